@@ -84,6 +84,91 @@ pub enum Action {
     FocusedTouchCombo,
     FocusedSynthesisCombo,
     HeartAndSoul,
+    // Dawntrail
+    ImmaculateMend,
+    TrainedPerfection,
+    DaringTouch,
+    QuickInnovation,
+    RefinedTouch,
+}
+
+/// Number of [Action] variants, used to size lookup tables indexed by action
+pub(crate) const ACTION_COUNT: usize = Action::ALL.len();
+
+impl Action {
+    /// Every action, in declaration order so `Action::ALL[action as usize] == action`
+    pub const ALL: [Action; 45] = [
+        Action::Observe,
+        Action::BasicSynth,
+        Action::BasicSynth2,
+        Action::CarefulSynthesis,
+        Action::CarefulObservation,
+        Action::RapidSynthesis,
+        Action::BasicTouch,
+        Action::StandardTouch,
+        Action::HastyTouch,
+        Action::ByregotsBlessing,
+        Action::MastersMend,
+        Action::TricksOfTheTrade,
+        Action::InnerQuiet,
+        Action::Manipulation,
+        Action::WasteNot,
+        Action::WasteNot2,
+        Action::Veneration,
+        Action::Innovation,
+        Action::GreatStrides,
+        Action::PreciseTouch,
+        Action::MuscleMemory,
+        Action::RapidSynthesis2,
+        Action::PrudentTouch,
+        Action::FocusedSynthesis,
+        Action::FocusedTouch,
+        Action::Reflect,
+        Action::PreparatoryTouch,
+        Action::Groundwork,
+        Action::DelicateSynthesis,
+        Action::IntensiveSynthesis,
+        Action::TrainedEye,
+        Action::CarefulSynthesis2,
+        Action::Groundwork2,
+        Action::AdvancedTouch,
+        Action::PrudentSynthesis,
+        Action::TrainedFinesse,
+        Action::FinalAppraisal,
+        Action::FocusedTouchCombo,
+        Action::FocusedSynthesisCombo,
+        Action::HeartAndSoul,
+        Action::ImmaculateMend,
+        Action::TrainedPerfection,
+        Action::DaringTouch,
+        Action::QuickInnovation,
+        Action::RefinedTouch,
+    ];
+}
+
+/// Rough grouping of actions by what they do to the craft
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ActionCategory {
+    Progress,
+    Touch,
+    Buff,
+    Repair,
+    Other,
+}
+
+impl Action {
+    pub fn category(&self) -> ActionCategory {
+        let details = self.details();
+        match self {
+            Action::MastersMend | Action::Manipulation | Action::ImmaculateMend => {
+                ActionCategory::Repair
+            }
+            _ if details.progress_increase_multiplier > 0.0 => ActionCategory::Progress,
+            _ if details.quality_increase_multiplier > 0.0 => ActionCategory::Touch,
+            _ if details.action_type != ActionType::Immediate => ActionCategory::Buff,
+            _ => ActionCategory::Other,
+        }
+    }
 }
 
 impl Display for Action {
@@ -703,6 +788,81 @@ impl Action {
             on_excellent: false,
             combo: None
         };
+        static REFINED_TOUCH: ActionDetails = ActionDetails {
+            short_name: "refinedTouch",
+            full_name: "Refined Touch",
+            durability_cost: 10,
+            cp_cost: 24,
+            success_probability: 1.0,
+            quality_increase_multiplier: 1.0,
+            progress_increase_multiplier: 0.0,
+            action_type: ActionType::Immediate,
+            class: "All",
+            level: 92,
+            on_good: false,
+            on_excellent: false,
+            combo: None,
+        };
+        static DARING_TOUCH: ActionDetails = ActionDetails {
+            short_name: "daringTouch",
+            full_name: "Daring Touch",
+            durability_cost: 10,
+            cp_cost: 0,
+            success_probability: 0.6,
+            quality_increase_multiplier: 1.5,
+            progress_increase_multiplier: 0.0,
+            action_type: ActionType::Immediate,
+            class: "All",
+            level: 96,
+            on_good: false,
+            on_excellent: false,
+            combo: None,
+        };
+        static QUICK_INNOVATION: ActionDetails = ActionDetails {
+            short_name: "quickInnovation",
+            full_name: "Quick Innovation",
+            durability_cost: 0,
+            cp_cost: 0,
+            success_probability: 1.0,
+            quality_increase_multiplier: 0.0,
+            progress_increase_multiplier: 0.0,
+            action_type: ActionType::Immediate,
+            class: "All",
+            level: 96,
+            on_good: false,
+            on_excellent: false,
+            combo: None,
+        };
+        static IMMACULATE_MEND: ActionDetails = ActionDetails {
+            short_name: "immaculateMend",
+            full_name: "Immaculate Mend",
+            durability_cost: 0,
+            cp_cost: 112,
+            success_probability: 1.0,
+            quality_increase_multiplier: 0.0,
+            progress_increase_multiplier: 0.0,
+            action_type: ActionType::Immediate,
+            class: "All",
+            level: 98,
+            on_good: false,
+            on_excellent: false,
+            combo: None,
+        };
+        static TRAINED_PERFECTION: ActionDetails = ActionDetails {
+            short_name: "trainedPerfection",
+            full_name: "Trained Perfection",
+            durability_cost: 0,
+            cp_cost: 0,
+            success_probability: 1.0,
+            quality_increase_multiplier: 0.0,
+            progress_increase_multiplier: 0.0,
+            action_type: ActionType::CountUp,
+            class: "All",
+            level: 100,
+            on_good: false,
+            on_excellent: false,
+            combo: None,
+        };
         match self {
             // observe: new Action(            'observe',              'Observe',               0,      7,  1.0, 0.0, 0.0, 'immediate',   1,  'All',          13),
             Action::Observe => &OBSERVE,
@@ -784,6 +944,17 @@ impl Action {
             Action::HeartAndSoul => &HEART_AND_SOUL,
             Action::FinalAppraisal => &FINAL_APPRAISAL,
             Action::CarefulObservation => &CAREFUL_OBSERVATION,
+            // Dawntrail
+            // refinedTouch: new Action(        'refinedTouch',         'Refined Touch',       10,     24,  1.0, 1.0, 0.0, 'immediate',   1,  'All',          92),
+            Action::RefinedTouch => &REFINED_TOUCH,
+            // daringTouch: new Action(         'daringTouch',          'Daring Touch',        10,      0,  0.6, 1.5, 0.0, 'immediate',   1,  'All',          96),
+            Action::DaringTouch => &DARING_TOUCH,
+            // quickInnovation: new Action(     'quickInnovation',      'Quick Innovation',     0,      0,  1.0, 0.0, 0.0, 'immediate',   1,  'All',          96),
+            Action::QuickInnovation => &QUICK_INNOVATION,
+            // immaculateMend: new Action(      'immaculateMend',       'Immaculate Mend',      0,    112,  1.0, 0.0, 0.0, 'immediate',   1,  'All',          98),
+            Action::ImmaculateMend => &IMMACULATE_MEND,
+            // trainedPerfection: new Action(   'trainedPerfection',    'Trained Perfection',   0,      0,  1.0, 0.0, 0.0, 'countup',     1,  'All',         100),
+            Action::TrainedPerfection => &TRAINED_PERFECTION,
         }
     }
 }
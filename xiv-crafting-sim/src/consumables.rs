@@ -0,0 +1,317 @@
+use crate::actions::Action;
+use crate::genome::{genome_from_actions, try_genome_from_actions};
+use crate::simulator::CalcState;
+use crate::solver::find_solution;
+use crate::stats::{with_stats, CrafterStats};
+use crate::xiv_model::{Crafter, LocalizedName, Synth};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::JsValue;
+
+/// A meal or medicine from `app/data/buffs`.
+/// Each stat goes up by `percent` of the crafter's base stat, but never by more than `value`.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Consumable {
+    pub id: String,
+    pub name: LocalizedName,
+    #[serde(default)]
+    pub hq: bool,
+    #[serde(default)]
+    pub craftsmanship_percent: u32,
+    #[serde(default)]
+    pub craftsmanship_value: u32,
+    #[serde(default)]
+    pub control_percent: u32,
+    #[serde(default)]
+    pub control_value: u32,
+    #[serde(default)]
+    pub cp_percent: u32,
+    #[serde(default)]
+    pub cp_value: u32,
+}
+
+/// `percent` of `base` capped at `max`, the same as `calcPercentMaxBonus` in the JS
+fn percent_max_bonus(base: u32, percent: u32, max: u32) -> u32 {
+    if percent == 0 || max == 0 {
+        return 0;
+    }
+    max.min(base * percent / 100)
+}
+
+impl Consumable {
+    /// Stats this consumable adds to the crafter
+    pub fn bonus_stats(&self, crafter: &Crafter) -> CrafterStats {
+        CrafterStats {
+            craftsmanship: percent_max_bonus(
+                crafter.craftsmanship,
+                self.craftsmanship_percent,
+                self.craftsmanship_value,
+            ),
+            control: percent_max_bonus(crafter.control, self.control_percent, self.control_value),
+            cp: percent_max_bonus(crafter.craft_points, self.cp_percent, self.cp_value),
+        }
+    }
+}
+
+/// Parses the contents of `Meal.json` or `Medicine.json`
+pub fn load_consumables(json: &str) -> Result<Vec<Consumable>, serde_json::Error> {
+    serde_json::from_str(json)
+}
+
+/// Reads `Meal.json` or `Medicine.json` from disk
+pub fn load_consumables_file(path: impl AsRef<Path>) -> std::io::Result<Vec<Consumable>> {
+    let json = std::fs::read_to_string(path)?;
+    Ok(load_consumables(&json)?)
+}
+
+/// A meal and medicine eaten together, either can be left out
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BuffSet {
+    pub meal: Option<Consumable>,
+    pub medicine: Option<Consumable>,
+}
+
+impl BuffSet {
+    /// The crafter's stats with both buffs applied. Both are worked out from the unbuffed stats, like the JS does.
+    pub fn apply(&self, crafter: &Crafter) -> CrafterStats {
+        let mut stats = CrafterStats {
+            craftsmanship: crafter.craftsmanship,
+            control: crafter.control,
+            cp: crafter.craft_points,
+        };
+        for buff in self.meal.iter().chain(&self.medicine) {
+            let bonus = buff.bonus_stats(crafter);
+            stats.craftsmanship += bonus.craftsmanship;
+            stats.control += bonus.control;
+            stats.cp += bonus.cp;
+        }
+        stats
+    }
+}
+
+/// A consumable the buff search may use and what it costs, in whatever unit the caller prices things in
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Candidate {
+    pub consumable: Consumable,
+    #[serde(default)]
+    pub cost: f32,
+}
+
+/// Every candidate plus going without
+fn or_none(candidates: &[Candidate]) -> impl Iterator<Item = Option<&Candidate>> {
+    std::iter::once(None).chain(candidates.iter().map(Some))
+}
+
+/// Tries every pairing of the candidate meals and medicines, including going without either, cheapest first.
+/// Returns the first set the rotation reaches max quality with, or the target tier's quality when the solver has one.
+/// An empty `actions` runs [find_solution] for each set instead of checking a fixed rotation, which needs a `maxLength`.
+/// Returns `None` if no set works, the rotation uses an action the crafter doesn't have,
+/// or there's no rotation and no `maxLength`.
+/// Equal costs prefer fewer consumables and then fewer HQ ones.
+pub fn find_cheapest_buffs(
+    synth: &Synth,
+    actions: &[Action],
+    meals: &[Candidate],
+    medicines: &[Candidate],
+) -> Option<BuffSet> {
+    let genome = if actions.is_empty() {
+        // the search runs once per set, it has to be bounded
+        if synth.max_length == 0 {
+            return None;
+        }
+        None
+    } else {
+        Some(try_genome_from_actions(synth, actions)?)
+    };
+    let mut sets: Vec<(Option<&Candidate>, Option<&Candidate>)> = vec![];
+    for meal in or_none(meals) {
+        for medicine in or_none(medicines) {
+            sets.push((meal, medicine));
+        }
+    }
+    let rank = |(meal, medicine): &(Option<&Candidate>, Option<&Candidate>)| {
+        let chosen = || meal.iter().chain(medicine);
+        let cost: f32 = chosen().map(|c| c.cost).sum();
+        let hq = chosen().filter(|c| c.consumable.hq).count();
+        (cost, chosen().count(), hq)
+    };
+    sets.sort_by(|a, b| {
+        rank(a)
+            .partial_cmp(&rank(b))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    sets.into_iter()
+        .map(|(meal, medicine)| BuffSet {
+            meal: meal.map(|c| c.consumable.clone()),
+            medicine: medicine.map(|c| c.consumable.clone()),
+        })
+        .find(|buffs| {
+            let synth = with_stats(synth, buffs.apply(&synth.crafter));
+            let state = match &genome {
+                Some(genome) => genome.calculate_final_state(&synth, &mut None),
+                None => genome_from_actions(&synth, &find_solution(&synth))
+                    .calculate_final_state(&synth, &mut None),
+            };
+            let violations = state.check_violations();
+            violations.progress_ok
+                && violations.durability_ok
+                && violations.cp_ok
+                && state.quality_state >= synth.target_quality() as i32
+        })
+}
+
+#[wasm_bindgen]
+pub fn find_cheapest_buffs_wasm(
+    synth: JsValue,
+    actions: JsValue,
+    meals: JsValue,
+    medicines: JsValue,
+) -> JsValue {
+    console_error_panic_hook::set_once();
+    let synth: Synth = serde_wasm_bindgen::from_value(synth).unwrap();
+    let actions: Vec<Action> = serde_wasm_bindgen::from_value(actions).unwrap();
+    let meals: Vec<Candidate> = serde_wasm_bindgen::from_value(meals).unwrap();
+    let medicines: Vec<Candidate> = serde_wasm_bindgen::from_value(medicines).unwrap();
+    serde_wasm_bindgen::to_value(&find_cheapest_buffs(&synth, &actions, &meals, &medicines))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::actions::Action;
+    use crate::consumables::{
+        find_cheapest_buffs, load_consumables, load_consumables_file, BuffSet, Candidate,
+        Consumable,
+    };
+    use crate::stats::{find_minimum_stats, FixedStats};
+    use crate::xiv_model::Synth;
+
+    const CRAFTER_SYNTH: &str = r#"{"crafter":{"level":78,"craftsmanship":863,"control":877,"cp":412,"actions":["muscleMemory","reflect","basicSynth2","carefulSynthesis","groundwork","intensiveSynthesis","delicateSynthesis","basicTouch","standardTouch","byregotsBlessing","preciseTouch","prudentTouch","preparatoryTouch","tricksOfTheTrade","mastersMend","wasteNot","wasteNot2","veneration","greatStrides","innovation","finalAppraisal","observe"]},"recipe":{"cls":"Weaver","level":390,"difficulty":1195,"durability":60,"startQuality":0,"safetyMargin":0,"maxQuality":3010,"baseLevel":71,"progressDivider":101,"progressModifier":100,"qualityDivider":81,"qualityModifier":100,"suggestedControl":1220,"suggestedCraftsmanship":1320,"name":"Custom Gathering Tool Components"},"sequence":[],"algorithm":"eaComplex","maxTricksUses":0,"maxMontecarloRuns":400,"reliabilityPercent":100,"useConditions":false,"maxLength":50,"solver":{"algorithm":"eaComplex","penaltyWeight":10000,"population":200000,"subPopulations":10,"solveForCompletion":false,"remainderCPFitnessValue":10,"remainderDurFitnessValue":100,"maxStagnationCounter":25,"generations":2000},"debug":true}"#;
+
+    const ROTATION: [Action; 14] = [
+        Action::MuscleMemory,
+        Action::Veneration,
+        Action::Groundwork,
+        Action::Innovation,
+        Action::PreparatoryTouch,
+        Action::MastersMend,
+        Action::PreparatoryTouch,
+        Action::GreatStrides,
+        Action::Innovation,
+        Action::ByregotsBlessing,
+        Action::MastersMend,
+        Action::Veneration,
+        Action::Groundwork,
+        Action::CarefulSynthesis,
+    ];
+
+    fn buff(
+        control_percent: u32,
+        control_value: u32,
+        cp_percent: u32,
+        cp_value: u32,
+    ) -> Consumable {
+        Consumable {
+            control_percent,
+            control_value,
+            cp_percent,
+            cp_value,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn loads_buff_files() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/../app/data/buffs/");
+        let meals = load_consumables_file(format!("{}Meal.json", dir)).unwrap();
+        let medicines = load_consumables_file(format!("{}Medicine.json", dir)).unwrap();
+        assert!(!meals.is_empty());
+        assert!(!medicines.is_empty());
+        let soup = meals
+            .iter()
+            .find(|meal| meal.name.en == "Baked Onion Soup" && meal.hq)
+            .unwrap();
+        assert_eq!(soup.cp_percent, 13);
+        assert_eq!(soup.cp_value, 48);
+        assert_eq!(soup.control_percent, 0);
+    }
+
+    #[test]
+    fn percent_capped_by_value() {
+        let synth: Synth = serde_json::from_str(CRAFTER_SYNTH).unwrap();
+        let meal = load_consumables(
+            r#"[{"id":"a","name":{"en":"Meal"},"cp_percent":10,"cp_value":30,"control_percent":5,"control_value":100}]"#,
+        )
+        .unwrap()
+        .remove(0);
+        let bonus = meal.bonus_stats(&synth.crafter);
+        // 10% of 412 is 41, capped at 30
+        assert_eq!(bonus.cp, 30);
+        // 5% of 877 rounds down to 43, under the cap
+        assert_eq!(bonus.control, 43);
+        assert_eq!(bonus.craftsmanship, 0);
+
+        // both buffs work from the unbuffed stats
+        let buffs = BuffSet {
+            meal: Some(meal.clone()),
+            medicine: Some(meal),
+        };
+        let stats = buffs.apply(&synth.crafter);
+        assert_eq!(stats.cp, 412 + 60);
+        assert_eq!(stats.control, 877 + 86);
+    }
+
+    #[test]
+    fn cheapest_buffs() {
+        let synth: Synth = serde_json::from_str(CRAFTER_SYNTH).unwrap();
+        let needed = find_minimum_stats(
+            &synth,
+            &ROTATION,
+            FixedStats {
+                craftsmanship: Some(863),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(needed.control > 877 || needed.cp > 412);
+
+        let plenty = buff(100, 1000, 100, 1000);
+        let meals = vec![
+            Candidate {
+                consumable: buff(1, 1, 1, 1),
+                cost: 1.0,
+            },
+            Candidate {
+                consumable: Consumable {
+                    hq: true,
+                    ..plenty.clone()
+                },
+                cost: 5.0,
+            },
+            Candidate {
+                consumable: plenty.clone(),
+                cost: 5.0,
+            },
+            Candidate {
+                consumable: plenty.clone(),
+                cost: 50.0,
+            },
+        ];
+        let buffs = find_cheapest_buffs(&synth, &ROTATION, &meals, &[]).unwrap();
+        assert_eq!(buffs.meal, Some(plenty));
+        assert_eq!(buffs.medicine, None);
+
+        assert_eq!(
+            find_cheapest_buffs(&synth, &ROTATION, &meals[..1], &[]),
+            None
+        );
+        // solving for every set needs a bound on the search
+        let mut unbounded = synth.clone();
+        unbounded.max_length = 0;
+        assert_eq!(find_cheapest_buffs(&unbounded, &[], &meals, &[]), None);
+    }
+}
@@ -0,0 +1,209 @@
+use crate::simulator::CrafterActions;
+use crate::xiv_model::{SimulationCondition, State, Synth};
+use genevo::genetic::{Children, Parents};
+use genevo::operator::prelude::SinglePointCrossBreeder;
+use genevo::operator::{CrossoverOp, GeneticOperator};
+use genevo::prelude::Rng;
+use serde::{Deserialize, Serialize};
+
+/// How the genetic solver combines two rotations
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Crossover {
+    /// genevo's single point crossover, both parents are cut at the same index
+    #[default]
+    SinglePoint,
+    /// Takes a slice from the middle of the second parent, cut at the same relative positions
+    TwoPoint,
+    /// Picks every gene from either parent at random
+    Uniform,
+    /// Cuts each parent where its quality phase starts, joining one parent's opener to the other's quality and finisher
+    Phase,
+}
+
+/// Crossover for rotations, every parent is paired with the next one to make a child
+#[derive(Clone, Debug)]
+pub(crate) struct RotationCrossBreeder {
+    crossover: Crossover,
+    single_point: SinglePointCrossBreeder,
+    // needed to find the phases of a rotation
+    synth: Synth,
+}
+
+impl RotationCrossBreeder {
+    pub(crate) fn new(synth: &Synth) -> Self {
+        Self {
+            crossover: synth.solver_vars.crossover,
+            single_point: SinglePointCrossBreeder::new(),
+            synth: synth.clone(),
+        }
+    }
+
+    fn two_point<R: Rng + Sized>(
+        first: &CrafterActions,
+        second: &CrafterActions,
+        rng: &mut R,
+    ) -> CrafterActions {
+        let mut start = rng.gen_range(0..=first.len());
+        let mut end = rng.gen_range(0..=first.len());
+        if start > end {
+            std::mem::swap(&mut start, &mut end);
+        }
+        // the same relative positions in the other parent, so the slice comes from the same part of the craft
+        let scale = |i: usize| i * second.len() / first.len().max(1);
+        first[..start]
+            .iter()
+            .chain(&second[scale(start)..scale(end)])
+            .chain(&first[end..])
+            .copied()
+            .collect()
+    }
+
+    fn uniform<R: Rng + Sized>(
+        first: &CrafterActions,
+        second: &CrafterActions,
+        rng: &mut R,
+    ) -> CrafterActions {
+        (0..first.len().max(second.len()))
+            .flat_map(|i| {
+                let parent = if rng.gen_bool(0.5) { first } else { second };
+                parent.get(i).copied()
+            })
+            .collect()
+    }
+
+    /// Index of the first gene that adds quality, the length of the rotation if none do
+    fn quality_phase_start(&self, genome: &CrafterActions) -> usize {
+        let synth = &self.synth;
+        let mut condition = SimulationCondition::new_sim_condition(synth);
+        let mut state = State::from(synth);
+        for (index, action) in genome
+            .iter()
+            .enumerate()
+            .flat_map(|(i, m)| Some((i, *synth.crafter.actions.get(*m as usize)?)))
+        {
+            let next = state.add_action(action, &mut condition);
+            if next.quality_state > state.quality_state {
+                return index;
+            }
+            if next.progress_state >= synth.recipe.difficulty as i32 || next.durability_state <= 0 {
+                break;
+            }
+            state = next;
+        }
+        genome.len()
+    }
+
+    fn phase(&self, first: &CrafterActions, second: &CrafterActions) -> CrafterActions {
+        let opener = &first[..self.quality_phase_start(first)];
+        let rest = &second[self.quality_phase_start(second)..];
+        opener.iter().chain(rest).copied().collect()
+    }
+}
+
+impl GeneticOperator for RotationCrossBreeder {
+    fn name() -> String {
+        "rotation cross breeder".to_string()
+    }
+}
+
+impl CrossoverOp<CrafterActions> for RotationCrossBreeder {
+    fn crossover<R>(
+        &self,
+        parents: Parents<CrafterActions>,
+        rng: &mut R,
+    ) -> Children<CrafterActions>
+    where
+        R: Rng + Sized,
+    {
+        if self.crossover == Crossover::SinglePoint {
+            return self.single_point.crossover(parents, rng);
+        }
+        let count = parents.len();
+        (0..count)
+            .map(|i| {
+                let first = &parents[i];
+                let second = &parents[(i + 1) % count];
+                let child = match self.crossover {
+                    Crossover::TwoPoint => Self::two_point(first, second, rng),
+                    Crossover::Uniform => Self::uniform(first, second, rng),
+                    _ => self.phase(first, second),
+                };
+                // an empty rotation can't be mutated or scored, keep the parent instead
+                if child.is_empty() {
+                    first.clone()
+                } else {
+                    child
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::crossover::{Crossover, RotationCrossBreeder};
+    use crate::simulator::CrafterActions;
+    use crate::xiv_model::Synth;
+    use genevo::operator::CrossoverOp;
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+    use smallvec::SmallVec;
+
+    const CRAFTER_SYNTH: &str = r#"{"crafter":{"level":78,"craftsmanship":863,"control":877,"cp":412,"actions":["muscleMemory","reflect","basicSynth2","carefulSynthesis","groundwork","intensiveSynthesis","delicateSynthesis","basicTouch","standardTouch","byregotsBlessing","preciseTouch","prudentTouch","preparatoryTouch","tricksOfTheTrade","mastersMend","wasteNot","wasteNot2","veneration","greatStrides","innovation","finalAppraisal","observe"]},"recipe":{"cls":"Weaver","level":390,"difficulty":1195,"durability":60,"startQuality":0,"safetyMargin":0,"maxQuality":3010,"baseLevel":71,"progressDivider":101,"progressModifier":100,"qualityDivider":81,"qualityModifier":100,"suggestedControl":1220,"suggestedCraftsmanship":1320,"name":"Custom Gathering Tool Components"},"sequence":[],"algorithm":"eaComplex","maxTricksUses":0,"maxMontecarloRuns":400,"reliabilityPercent":100,"useConditions":false,"maxLength":50,"solver":{"algorithm":"eaComplex","penaltyWeight":10000,"population":200000,"subPopulations":10,"solveForCompletion":false,"remainderCPFitnessValue":10,"remainderDurFitnessValue":100,"maxStagnationCounter":25,"generations":2000},"debug":true}"#;
+
+    fn breeder(crossover: Crossover) -> RotationCrossBreeder {
+        let mut synth: Synth = serde_json::from_str(CRAFTER_SYNTH).unwrap();
+        synth.solver_vars.crossover = crossover;
+        RotationCrossBreeder::new(&synth)
+    }
+
+    #[test]
+    fn parses_crossover() {
+        let synth: Synth = serde_json::from_str(&CRAFTER_SYNTH.replace(
+            r#""generations":2000"#,
+            r#""generations":2000,"crossover":"twoPoint""#,
+        ))
+        .unwrap();
+        assert_eq!(synth.solver_vars.crossover, Crossover::TwoPoint);
+    }
+
+    #[test]
+    fn children_only_use_parent_genes() {
+        let parents: Vec<CrafterActions> = vec![
+            SmallVec::from_slice(&[0, 1, 2, 3, 4]),
+            SmallVec::from_slice(&[10, 11, 12]),
+        ];
+        let mut rng = SmallRng::seed_from_u64(5);
+        for crossover in [Crossover::TwoPoint, Crossover::Uniform] {
+            let breeder = breeder(crossover);
+            for _ in 0..50 {
+                let children = breeder.crossover(parents.clone(), &mut rng);
+                assert_eq!(children.len(), parents.len());
+                for child in children {
+                    assert!(!child.is_empty());
+                    assert!(child.len() <= 5 + 3);
+                    assert!(child.iter().all(|g| parents.iter().any(|p| p.contains(g))));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn phase_crossover() {
+        // muscle memory, veneration, groundwork | basic touch, standard touch, careful synthesis
+        let first: CrafterActions = SmallVec::from_slice(&[0, 17, 4, 7, 8, 3]);
+        // reflect, basic touch, byregots | careful synthesis
+        let second: CrafterActions = SmallVec::from_slice(&[1, 7, 9, 3]);
+        let breeder = breeder(Crossover::Phase);
+        assert_eq!(breeder.quality_phase_start(&first), 3);
+        assert_eq!(breeder.quality_phase_start(&second), 0);
+        let children = breeder.crossover(
+            vec![first.clone(), second.clone()],
+            &mut SmallRng::seed_from_u64(1),
+        );
+        // the opener of one parent followed by the quality and finisher of the other
+        assert_eq!(children[0].as_slice(), &[0, 17, 4, 1, 7, 9, 3]);
+        assert_eq!(children[1].as_slice(), &[7, 8, 3]);
+    }
+}
@@ -1,45 +1,40 @@
-use crate::actions::Action;
-use serde::Serialize;
+use crate::actions::{Action, ACTION_COUNT};
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
 use std::fmt::{Display, Formatter};
 use std::iter::FromIterator;
 
 /// Effect tracker is a key value store
-/// Data is a contiguous slice of memory, if we somehow have more than abilities than space, please just increase this and don't look back.
-#[derive(Default, Debug, Serialize, Clone)]
-pub struct EffectData([Option<(Action, i8)>; 4]);
+/// Data is a dense array with one slot per action, so every buff can be active at once and lookups are just an index.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EffectData([Option<(Action, i8)>; ACTION_COUNT]);
+
+impl Default for EffectData {
+    fn default() -> Self {
+        Self([None; ACTION_COUNT])
+    }
+}
 
 impl EffectData {
     pub(crate) fn get_mut(&mut self, action: Action) -> Option<&mut i8> {
-        self.0
-            .iter_mut()
-            .flatten()
-            .find(|(a, _)| *a == action)
-            .map(|(_, i)| i)
+        self.0[action as usize].as_mut().map(|(_, i)| i)
     }
 
     pub(crate) fn get(&self, action: Action) -> Option<&(Action, i8)> {
-        self.0.iter().flatten().find(|(a, _)| *a == action)
+        self.0[action as usize].as_ref()
     }
 
     pub(crate) fn remove(&mut self, action: Action) {
-        // there shouldn't be multiple actions, but just in case use filter and clear all of them
-        for value in self
-            .0
-            .iter_mut()
-            .filter(|m| m.map(|(a, _)| a.eq(&action)).unwrap_or_default())
-        {
-            *value = None;
-        }
+        self.0[action as usize] = None;
     }
 
-    /// Insert all values
-    pub(crate) fn insert(&mut self, action: Action, value: i8) -> bool {
-        if let Some(v) = self.0.iter_mut().find(|f| f.is_none()) {
-            *v = Some((action, value));
-            true
-        } else {
-            false
-        }
+    /// Sets the value for an action, replacing the old value if it was already active
+    pub(crate) fn insert(&mut self, action: Action, value: i8) {
+        self.0[action as usize] = Some((action, value));
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (Action, i8)> + '_ {
+        self.0.iter().flatten().copied()
     }
 
     pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = &mut Option<(Action, i8)>> {
@@ -47,9 +42,19 @@ impl EffectData {
     }
 }
 
+impl Serialize for EffectData {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        for (action, count) in self.iter() {
+            map.serialize_entry(&action, &count)?;
+        }
+        map.end()
+    }
+}
+
 impl Display for EffectData {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        for (action, count) in self.0.into_iter().flatten() {
+        for (action, count) in self.iter() {
             write!(f, "{:?}:{}", action, count)?; // using debug version of Action can help see BasicSynth2
         }
         Ok(())
@@ -65,3 +70,51 @@ impl FromIterator<(Action, i32)> for EffectData {
         effects
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::actions::Action;
+    use crate::effect_tracker::EffectData;
+
+    #[test]
+    fn action_index() {
+        for (i, action) in Action::ALL.iter().enumerate() {
+            assert_eq!(*action as usize, i);
+        }
+    }
+
+    #[test]
+    fn many_buffs() {
+        let buffs = [
+            (Action::Veneration, 4),
+            (Action::Innovation, 4),
+            (Action::GreatStrides, 3),
+            (Action::WasteNot2, 8),
+            (Action::Manipulation, 8),
+            (Action::FinalAppraisal, 5),
+            (Action::MuscleMemory, 5),
+        ];
+        let mut effects: EffectData = buffs.into_iter().collect();
+        for (action, turns) in buffs {
+            assert_eq!(effects.get(action), Some(&(action, turns as i8)));
+        }
+        assert_eq!(effects.iter().count(), buffs.len());
+
+        // reapplying a buff refreshes it instead of taking another slot
+        effects.insert(Action::Veneration, 2);
+        effects.insert(Action::Veneration, 4);
+        assert_eq!(
+            effects.get(Action::Veneration),
+            Some(&(Action::Veneration, 4))
+        );
+        assert_eq!(effects.iter().count(), buffs.len());
+
+        effects.remove(Action::GreatStrides);
+        assert!(effects.get(Action::GreatStrides).is_none());
+        *effects.get_mut(Action::Manipulation).unwrap() -= 1;
+        assert_eq!(
+            effects.get(Action::Manipulation),
+            Some(&(Action::Manipulation, 7))
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use crate::actions::Action;
 use crate::xiv_model::Synth;
 use genevo::prelude::{GenomeBuilder, Rng};
 use genevo::random::SampleUniform;
@@ -11,6 +12,10 @@ pub struct CraftActionGenomeBuilder<V> {
     max_length: usize,
     min_value: V,
     max_value: V,
+    /// Genomes the seeded individuals start from
+    seed_genomes: Vec<SmallVec<[V; 128]>>,
+    /// Number of individuals built from the seed genomes
+    seeded_count: usize,
 }
 
 impl<V> CraftActionGenomeBuilder<V> {
@@ -31,21 +36,106 @@ impl<V> CraftActionGenomeBuilder<V> {
             max_length,
             min_value,
             max_value,
+            seed_genomes: vec![],
+            seeded_count: 0,
         }
     }
+
+    /// Builds the first `count` individuals from `genomes` instead of at random, taking turns between them.
+    /// Each genome gets one exact copy, the rest are mutated variants.
+    pub(crate) fn with_seed_genomes(
+        mut self,
+        genomes: Vec<SmallVec<[V; 128]>>,
+        count: usize,
+    ) -> Self {
+        self.seed_genomes = genomes.into_iter().filter(|g| !g.is_empty()).collect();
+        self.seeded_count = if self.seed_genomes.is_empty() {
+            0
+        } else {
+            count.max(self.seed_genomes.len())
+        };
+        self
+    }
+}
+
+/// Translates actions into genome indices, actions the crafter doesn't have are dropped
+pub(crate) fn genome_from_actions(synth: &Synth, actions: &[Action]) -> CrafterActions {
+    actions
+        .iter()
+        .filter_map(|action| synth.crafter.actions.iter().position(|a| a == action))
+        .map(|index| index as u8)
+        .collect()
+}
+
+/// Translates actions into genome indices, `None` if the crafter doesn't have one of them
+pub(crate) fn try_genome_from_actions(synth: &Synth, actions: &[Action]) -> Option<CrafterActions> {
+    actions
+        .iter()
+        .map(|action| {
+            let index = synth.crafter.actions.iter().position(|a| a == action)?;
+            Some(index as u8)
+        })
+        .collect()
 }
 
 impl<V> GenomeBuilder<SmallVec<[V; 128]>> for CraftActionGenomeBuilder<V>
 where
     V: Debug + PartialEq + PartialOrd + SampleUniform + Send + Sync + Copy,
 {
-    fn build_genome<R>(&self, _: usize, rng: &mut R) -> SmallVec<[V; 128]>
+    fn build_genome<R>(&self, index: usize, rng: &mut R) -> SmallVec<[V; 128]>
     where
         R: Rng + Sized,
     {
+        if index < self.seeded_count {
+            let seed = &self.seed_genomes[index % self.seed_genomes.len()];
+            let mut genome = seed.clone();
+            // a handful of random edits, the same as the mutator would make
+            let edits = if index < self.seed_genomes.len() {
+                0
+            } else {
+                rng.gen_range(1..=3)
+            };
+            for _ in 0..edits {
+                let value = rng.gen_range(self.min_value..self.max_value);
+                match rng.gen_range(0..=5) {
+                    0 if genome.len() > 1 => {
+                        genome.remove(rng.gen_range(0..genome.len()));
+                    }
+                    5 if genome.len() < self.max_length.max(seed.len()) => {
+                        genome.insert(rng.gen_range(0..=genome.len()), value);
+                    }
+                    _ => {
+                        let i = rng.gen_range(0..genome.len());
+                        genome[i] = value;
+                    }
+                }
+            }
+            return genome;
+        }
         let random_length = rng.gen_range(self.min_length..=self.max_length);
         (0..random_length)
             .map(|_| rng.gen_range(self.min_value..self.max_value))
             .collect()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::actions::Action;
+    use crate::genome::try_genome_from_actions;
+    use crate::xiv_model::Synth;
+
+    const SMOL_ABILITY: &str = r#"{"crafter":{"level":9,"craftsmanship":100,"control":100,"cp":180,"actions":["basicSynth","basicTouch","mastersMend"]},"recipe":{"baseLevel":10,"difficulty":45,"durability":60,"level":10,"maxQuality":250,"progressDivider":50,"progressModifier":100,"qualityDivider":30,"qualityModifier":100,"suggestedControl":29,"suggestedCraftsmanship":59,"name":"Heat Vent Component","cls":"Culinarian","startQuality":0,"safetyMargin":0},"sequence":[],"algorithm":"eaComplex","maxTricksUses":0,"maxMontecarloRuns":400,"reliabilityPercent":100,"useConditions":false,"maxLength":8,"solver":{"algorithm":"eaComplex","penaltyWeight":10000,"population":10000,"subPopulations":10,"solveForCompletion":false,"remainderCPFitnessValue":10,"remainderDurFitnessValue":100,"maxStagnationCounter":25,"generations":1000},"debug":true}"#;
+
+    #[test]
+    fn rejects_missing_actions() {
+        let synth: Synth = serde_json::from_str(SMOL_ABILITY).unwrap();
+        let genome = try_genome_from_actions(&synth, &[Action::BasicTouch, Action::BasicSynth]);
+        assert_eq!(genome.unwrap().to_vec(), vec![1, 0]);
+        // hasty touch isn't one of the crafter's actions, dropping it would give a different rotation
+        assert_eq!(
+            try_genome_from_actions(&synth, &[Action::BasicTouch, Action::HastyTouch]),
+            None
+        );
+    }
+}
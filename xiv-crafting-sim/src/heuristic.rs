@@ -0,0 +1,202 @@
+use crate::actions::Action;
+use crate::xiv_model::Synth;
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::JsValue;
+
+/// Keeps track of the cp and durability the sequence has used so far
+struct Budget<'a> {
+    actions: &'a [Action],
+    cp: i32,
+    durability: i32,
+}
+
+impl Budget<'_> {
+    fn has_action(&self, action: Action) -> bool {
+        self.actions.contains(&action)
+    }
+
+    fn try_action(&self, action: Action) -> bool {
+        let details = action.details();
+        self.has_action(action)
+            && self.cp >= details.cp_cost
+            && self.durability - details.durability_cost >= 0
+    }
+
+    fn use_action(&mut self, action: Action) {
+        let details = action.details();
+        self.cp -= details.cp_cost;
+        self.durability -= details.durability_cost;
+    }
+
+    fn push_action(&mut self, sequence: &mut Vec<Action>, action: Action) {
+        sequence.push(action);
+        self.use_action(action);
+    }
+
+    fn unshift_action(&mut self, sequence: &mut Vec<Action>, action: Action) {
+        sequence.insert(0, action);
+        self.use_action(action);
+    }
+}
+
+/// Port of `heuristicSequenceBuilder` from the JS model.
+/// Builds a rough sequence from the crafter's actions, cp and the recipe's durability:
+/// an opener, as many touches as the budget allows, Byregot's Blessing and enough synthesis to finish.
+/// It doesn't simulate anything, so the result is a starting point rather than a good macro.
+pub fn heuristic_sequence_builder(synth: &Synth) -> Vec<Action> {
+    let mut budget = Budget {
+        actions: &synth.crafter.actions,
+        cp: synth.crafter.craft_points as i32,
+        durability: synth.recipe.durability as i32,
+    };
+
+    /* Progress to completion
+        -- Determine base progress
+        -- Determine best action to use from available list
+        -- Master's mend if more steps are needed
+    */
+
+    // If Careful Synthesis is available, use it
+    let mut preferred_action = Action::BasicSynth;
+    if budget.has_action(Action::CarefulSynthesis) {
+        preferred_action = Action::CarefulSynthesis;
+    }
+
+    // Determine base progress
+    let (base_progress, _) = synth.calculate_progress_and_quality_increase();
+    let progress_gain =
+        (base_progress as f32 * preferred_action.details().progress_increase_multiplier) as u32;
+    let progress_steps = synth.recipe.difficulty.div_ceil(progress_gain.max(1));
+    let mut progress = 0;
+    let mut steps = 0;
+
+    // Final step first
+    let mut finisher = vec![];
+    if budget.try_action(preferred_action) {
+        budget.push_action(&mut finisher, preferred_action);
+        progress += progress_gain;
+        steps += 1;
+    }
+
+    let mut synthesis = vec![];
+    while progress < synth.recipe.difficulty && steps < progress_steps {
+        // Don't want to increase progress at 5 durability unless we are able to complete the synth
+        if budget.try_action(preferred_action) && budget.durability >= 10 {
+            budget.unshift_action(&mut synthesis, preferred_action);
+            progress += progress_gain;
+            steps += 1;
+        } else if budget.try_action(Action::Manipulation) {
+            budget.unshift_action(&mut synthesis, Action::Manipulation);
+            budget.durability += 30;
+        } else if budget.try_action(Action::MastersMend) {
+            budget.unshift_action(&mut synthesis, Action::MastersMend);
+            budget.durability += 30;
+        } else {
+            break;
+        }
+    }
+
+    let mut sequence = synthesis;
+    sequence.extend(finisher);
+
+    if budget.durability <= 20 {
+        if budget.try_action(Action::Manipulation) {
+            budget.unshift_action(&mut sequence, Action::Manipulation);
+            budget.durability += 30;
+        } else if budget.try_action(Action::MastersMend) {
+            budget.unshift_action(&mut sequence, Action::MastersMend);
+            budget.durability += 30;
+        }
+    }
+
+    /* Improve Quality
+     -- Reflect and Inner Quiet at start
+     -- Byregot's at end or other Inner Quiet consumer
+    */
+
+    let mut opener = vec![];
+    if budget.try_action(Action::Reflect) {
+        budget.push_action(&mut opener, Action::Reflect);
+    }
+
+    if budget.try_action(Action::InnerQuiet) {
+        budget.push_action(&mut opener, Action::InnerQuiet);
+    }
+
+    let preferred_action = Action::BasicTouch;
+
+    // ... and put in at least one quality improving action
+    if budget.try_action(preferred_action) {
+        budget.push_action(&mut opener, preferred_action);
+    }
+
+    // Now add in Byregot's Blessing at the end of the quality improving stage if we can
+    if budget.try_action(Action::ByregotsBlessing) {
+        budget.unshift_action(&mut sequence, Action::ByregotsBlessing);
+    }
+
+    // ... and what the hell, throw in a great strides just before it
+    if budget.try_action(Action::GreatStrides) {
+        budget.unshift_action(&mut sequence, Action::GreatStrides);
+    }
+
+    // Use up any remaining durability and cp with quality / durability improving actions
+    let mut touches = vec![];
+    while budget.cp > 0 && budget.durability > 0 {
+        if budget.try_action(preferred_action) && budget.durability > 10 {
+            budget.push_action(&mut touches, preferred_action);
+        } else if budget.durability < 20 {
+            if budget.try_action(Action::Manipulation) {
+                budget.unshift_action(&mut touches, Action::Manipulation);
+                budget.durability += 30;
+            } else if budget.try_action(Action::MastersMend) {
+                budget.push_action(&mut touches, Action::MastersMend);
+                budget.durability += 30;
+            } else {
+                break;
+            }
+        } else {
+            break;
+        }
+    }
+
+    // Pray
+    opener.extend(touches);
+    opener.extend(sequence);
+    opener
+}
+
+#[wasm_bindgen]
+pub fn heuristic_sequence_builder_wasm(synth: JsValue) -> JsValue {
+    console_error_panic_hook::set_once();
+    let synth: Synth = serde_wasm_bindgen::from_value(synth).unwrap();
+    serde_wasm_bindgen::to_value(&heuristic_sequence_builder(&synth)).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::actions::Action;
+    use crate::heuristic::heuristic_sequence_builder;
+    use crate::simulation::simulate_sequence;
+    use crate::xiv_model::Synth;
+
+    const CRAFTER_SYNTH: &str = r#"{"crafter":{"level":78,"craftsmanship":863,"control":877,"cp":412,"actions":["muscleMemory","reflect","basicSynth2","carefulSynthesis","groundwork","intensiveSynthesis","delicateSynthesis","basicTouch","standardTouch","byregotsBlessing","preciseTouch","prudentTouch","preparatoryTouch","tricksOfTheTrade","mastersMend","wasteNot","wasteNot2","veneration","greatStrides","innovation","finalAppraisal","observe"]},"recipe":{"cls":"Weaver","level":390,"difficulty":1195,"durability":60,"startQuality":0,"safetyMargin":0,"maxQuality":3010,"baseLevel":71,"progressDivider":101,"progressModifier":100,"qualityDivider":81,"qualityModifier":100,"suggestedControl":1220,"suggestedCraftsmanship":1320,"name":"Custom Gathering Tool Components"},"sequence":[],"algorithm":"eaComplex","maxTricksUses":0,"maxMontecarloRuns":400,"reliabilityPercent":100,"useConditions":false,"maxLength":50,"solver":{"algorithm":"eaComplex","penaltyWeight":10000,"population":200000,"subPopulations":10,"solveForCompletion":false,"remainderCPFitnessValue":10,"remainderDurFitnessValue":100,"maxStagnationCounter":25,"generations":2000},"debug":true}"#;
+
+    #[test]
+    fn builds_feasible_sequence() {
+        let synth: Synth = serde_json::from_str(CRAFTER_SYNTH).unwrap();
+        let sequence = heuristic_sequence_builder(&synth);
+        assert_eq!(sequence.first(), Some(&Action::Reflect));
+        assert_eq!(sequence.last(), Some(&Action::CarefulSynthesis));
+        assert!(sequence.contains(&Action::ByregotsBlessing));
+        assert!(sequence
+            .iter()
+            .all(|action| synth.crafter.actions.contains(action)));
+
+        let last = simulate_sequence(&synth, &sequence).pop().unwrap();
+        assert!(last.violations.progress_ok);
+        assert!(last.violations.durability_ok);
+        assert!(last.violations.cp_ok);
+        assert!(last.quality > 0);
+    }
+}
@@ -0,0 +1,50 @@
+/// Fitted curve mapping an HQ chance to the percentage of max quality needed for it
+pub fn quality_from_hq_percent(hq_percent: f64) -> f64 {
+    let x = hq_percent;
+    -5.6604E-6 * x.powi(4) + 0.0015369705 * x.powi(3) - 0.1426469573 * x.powi(2) + 5.6122722959 * x
+        - 5.5950384565
+}
+
+/// Inverse of [quality_from_hq_percent], walks the curve up until the quality percent is reached
+pub fn hq_percent_from_quality(quality_percent: f64) -> u32 {
+    if quality_percent <= 0.0 {
+        return 1;
+    }
+    if quality_percent >= 100.0 {
+        return 100;
+    }
+    let mut hq_percent = 1;
+    while quality_from_hq_percent(hq_percent as f64) < quality_percent && hq_percent < 100 {
+        hq_percent += 1;
+    }
+    hq_percent
+}
+
+/// HQ chance for a craft finishing with `quality` out of the recipe's `max_quality`
+pub fn hq_percent(quality: i32, max_quality: u32) -> u32 {
+    if max_quality == 0 {
+        return 100;
+    }
+    hq_percent_from_quality(quality as f64 / max_quality as f64 * 100.0)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::hq::{hq_percent, hq_percent_from_quality};
+
+    #[test]
+    fn matches_js_curve() {
+        assert_eq!(hq_percent_from_quality(0.0), 1);
+        assert_eq!(hq_percent_from_quality(50.0), 15);
+        assert_eq!(hq_percent_from_quality(90.0), 86);
+        assert_eq!(hq_percent_from_quality(100.0), 100);
+        assert_eq!(hq_percent_from_quality(130.0), 100);
+    }
+
+    #[test]
+    fn quality_to_hq() {
+        assert_eq!(hq_percent(1473, 3010), 14);
+        assert_eq!(hq_percent(3010, 3010), 100);
+        assert_eq!(hq_percent(5000, 3010), 100);
+    }
+}
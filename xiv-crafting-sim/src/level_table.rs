@@ -40,6 +40,35 @@ pub(crate) fn level_table_lookup(level: u32) -> u32 {
         88 => 550,
         89 => 555,
         90 => 560,
+        // 91-100 are the CraftingLevel column of the ParamGrow sheet from the 7.0 game data.
+        // Level 100 jumps to 690 to line up with the recipe level of the level 100 recipes,
+        // the way 90 lines up with the 560 of the level 90 ones.
+        91 => 570,
+        92 => 580,
+        93 => 590,
+        94 => 600,
+        95 => 610,
+        96 => 620,
+        97 => 630,
+        98 => 640,
+        99 => 650,
+        100 => 690,
         _ => level,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::level_table::level_table_lookup;
+
+    #[test]
+    fn levels_only_go_up() {
+        for level in 51..100 {
+            assert!(level_table_lookup(level) < level_table_lookup(level + 1));
+        }
+        assert_eq!(level_table_lookup(50), 50);
+        assert_eq!(level_table_lookup(90), 560);
+        assert_eq!(level_table_lookup(99), 650);
+        assert_eq!(level_table_lookup(100), 690);
+    }
+}
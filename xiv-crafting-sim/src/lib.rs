@@ -1,15 +1,43 @@
 mod actions;
+pub mod consumables;
+mod crossover;
 mod effect_tracker;
 mod genome;
+pub mod heuristic;
+pub mod hq;
 mod level_table;
+mod local_search;
+pub mod monte_carlo;
 mod mutator;
+pub mod pareto;
+pub mod recipedb;
+pub mod simulation;
 pub mod simulator;
+pub mod solver;
+pub mod stats;
 mod xiv_model;
 
 
-pub use xiv_model::Synth;
-pub use simulator::CraftSimulator;
+pub use xiv_model::{
+    ActionCosts, Algorithm, ClassStats, Condition, CrafterProfile, CraftingClass, FitnessConfig,
+    LocalizedName, QualityTier, Recipe, RobustFitness, SolverVars, Synth,
+};
+pub use simulator::{CraftSimulator, SolverBackend};
 pub use actions::Action;
+pub use simulation::{Simulation, StepSnapshot};
+pub use monte_carlo::{monte_carlo_sim, MonteCarloReport};
+pub use solver::find_solution;
+pub use consumables::{find_cheapest_buffs, BuffSet, Candidate, Consumable};
+pub use recipedb::{RecipeDb, RecipeInfo, RecipeQuery};
+pub use stats::{
+    find_minimum_stats, sweep_stats, CrafterStats, FixedStats, Stat, StatRange, SweepReport,
+};
+pub use heuristic::heuristic_sequence_builder;
+pub use local_search::polish_sequence;
+pub use crossover::Crossover;
+pub use mutator::MutationWeights;
+pub use pareto::ParetoPoint;
+pub use hq::{hq_percent, hq_percent_from_quality, quality_from_hq_percent};
 // used by js to initialize rayon
 #[cfg(feature = "wasm-thread")]
 pub use wasm_bindgen_rayon::init_thread_pool;
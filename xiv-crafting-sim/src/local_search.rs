@@ -0,0 +1,157 @@
+use crate::actions::Action;
+use crate::genome::{genome_from_actions, try_genome_from_actions};
+use crate::simulator::{CalcState, CrafterActions};
+use crate::xiv_model::Synth;
+use genevo::genetic::FitnessFunction;
+
+/// Every rotation one edit away: a single deletion, a single substitution or swapping two neighbours
+fn neighbours(genome: &CrafterActions, values: u8) -> impl Iterator<Item = CrafterActions> + '_ {
+    let length = genome.len();
+    let deletions = (0..length).filter(move |_| length > 1).map(move |i| {
+        let mut candidate = genome.clone();
+        candidate.remove(i);
+        candidate
+    });
+    let substitutions = (0..length).flat_map(move |i| {
+        (0..values).filter(move |v| *v != genome[i]).map(move |v| {
+            let mut candidate = genome.clone();
+            candidate[i] = v;
+            candidate
+        })
+    });
+    let swaps = (1..length)
+        .filter(move |i| genome[i - 1] != genome[*i])
+        .map(move |i| {
+            let mut candidate = genome.clone();
+            candidate.swap(i - 1, i);
+            candidate
+        });
+    deletions.chain(substitutions).chain(swaps)
+}
+
+/// Hill climbs from `genome` using the solver's fitness, taking the best single edit until none of them improve.
+/// Genes after the craft has ended don't change the fitness, so they are dropped first,
+/// unless dropping them scores lower and the search starts from `genome` as it is.
+/// The robust fitness is left out, running every neighbour through all of its trials is too slow.
+pub(crate) fn polish(synth: &Synth, genome: &CrafterActions) -> CrafterActions {
+    let mut synth = synth.clone();
+    synth.solver_vars.fitness.robust = None;
+    let synth = &synth;
+    let (_, actions) = genome.get_final_actions_list(synth, &mut None);
+    let values = synth.crafter.actions.len() as u8;
+    let mut best = genome.clone();
+    let mut best_fitness = synth.fitness_of(genome);
+    let trimmed = genome_from_actions(synth, &actions);
+    if !trimmed.is_empty() && synth.fitness_of(&trimmed) >= best_fitness {
+        best_fitness = synth.fitness_of(&trimmed);
+        best = trimmed;
+    }
+    loop {
+        let mut improved = None;
+        for candidate in neighbours(&best, values) {
+            let fitness = synth.fitness_of(&candidate);
+            if fitness > best_fitness {
+                best_fitness = fitness;
+                improved = Some(candidate);
+            }
+        }
+        match improved {
+            Some(candidate) => best = candidate,
+            None => return best,
+        }
+    }
+}
+
+/// Runs the same local search the genetic solver finishes with on any sequence.
+/// Returns `None` if the sequence uses an action the crafter doesn't have.
+pub fn polish_sequence(synth: &Synth, actions: &[Action]) -> Option<Vec<Action>> {
+    let genome = try_genome_from_actions(synth, actions)?;
+    Some(polish(synth, &genome).get_actions_list(synth))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::actions::Action;
+    use crate::genome::genome_from_actions;
+    use crate::local_search::{neighbours, polish, polish_sequence};
+    use crate::simulator::CalcState;
+    use crate::xiv_model::{RobustFitness, Synth};
+    use genevo::genetic::FitnessFunction;
+
+    const CRAFTER_SYNTH: &str = r#"{"crafter":{"level":78,"craftsmanship":863,"control":877,"cp":412,"actions":["muscleMemory","reflect","basicSynth2","carefulSynthesis","groundwork","intensiveSynthesis","delicateSynthesis","basicTouch","standardTouch","byregotsBlessing","preciseTouch","prudentTouch","preparatoryTouch","tricksOfTheTrade","mastersMend","wasteNot","wasteNot2","veneration","greatStrides","innovation","finalAppraisal","observe"]},"recipe":{"cls":"Weaver","level":390,"difficulty":1195,"durability":60,"startQuality":0,"safetyMargin":0,"maxQuality":3010,"baseLevel":71,"progressDivider":101,"progressModifier":100,"qualityDivider":81,"qualityModifier":100,"suggestedControl":1220,"suggestedCraftsmanship":1320,"name":"Custom Gathering Tool Components"},"sequence":[],"algorithm":"eaComplex","maxTricksUses":0,"maxMontecarloRuns":400,"reliabilityPercent":100,"useConditions":false,"maxLength":50,"solver":{"algorithm":"eaComplex","penaltyWeight":10000,"population":200000,"subPopulations":10,"solveForCompletion":false,"remainderCPFitnessValue":10,"remainderDurFitnessValue":100,"maxStagnationCounter":25,"generations":2000},"debug":true}"#;
+
+    #[test]
+    fn reaches_local_optimum() {
+        let synth: Synth = serde_json::from_str(CRAFTER_SYNTH).unwrap();
+        let genome = genome_from_actions(
+            &synth,
+            &[
+                Action::Reflect,
+                Action::Observe,
+                Action::BasicTouch,
+                Action::BasicTouch,
+                Action::Groundwork,
+                Action::Groundwork,
+                Action::CarefulSynthesis,
+            ],
+        );
+        let polished = polish(&synth, &genome);
+        let fitness = synth.fitness_of(&polished);
+        assert!(fitness > synth.fitness_of(&genome));
+        let values = synth.crafter.actions.len() as u8;
+        assert!(neighbours(&polished, values).all(|n| synth.fitness_of(&n) <= fitness));
+    }
+
+    #[test]
+    fn drops_actions_after_the_craft() {
+        let synth: Synth = serde_json::from_str(CRAFTER_SYNTH).unwrap();
+        let polished = polish_sequence(
+            &synth,
+            &[
+                Action::MuscleMemory,
+                Action::Veneration,
+                Action::Groundwork,
+                Action::Groundwork,
+                Action::CarefulSynthesis,
+                Action::BasicTouch,
+                Action::BasicTouch,
+            ],
+        )
+        .unwrap();
+        assert!(polished.len() <= 5);
+    }
+
+    #[test]
+    fn ignores_robust_fitness() {
+        let synth: Synth = serde_json::from_str(CRAFTER_SYNTH).unwrap();
+        let mut robust = synth.clone();
+        robust.solver_vars.fitness.robust = Some(RobustFitness::default());
+        let genome = genome_from_actions(
+            &synth,
+            &[Action::Reflect, Action::BasicTouch, Action::Groundwork],
+        );
+        assert_eq!(polish(&robust, &genome), polish(&synth, &genome));
+    }
+
+    #[test]
+    fn keeps_careful_observation_rotations() {
+        let mut synth: Synth = serde_json::from_str(CRAFTER_SYNTH).unwrap();
+        synth.crafter.specialist = true;
+        synth.crafter.actions.push(Action::CarefulObservation);
+        let sequence = [
+            Action::CarefulObservation,
+            Action::MuscleMemory,
+            Action::Veneration,
+            Action::Groundwork,
+            Action::Groundwork,
+            Action::CarefulSynthesis,
+        ];
+        let genome = genome_from_actions(&synth, &sequence);
+        // careful observation doesn't take a step, the actions after it still have to come back
+        let (state, actions) = genome.get_final_actions_list(&synth, &mut None);
+        assert_eq!(actions.len(), state.actions_used());
+        assert_eq!(actions[0], Action::CarefulObservation);
+        let polished = polish(&synth, &genome);
+        assert!(synth.fitness_of(&polished) >= synth.fitness_of(&genome));
+    }
+}
@@ -0,0 +1,231 @@
+use crate::actions::Action;
+use crate::hq::hq_percent;
+use crate::xiv_model::{RobustFitness, SimulationCondition, State, Synth};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::JsValue;
+
+/// Summary of running the same sequence many times with random conditions and success rolls
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MonteCarloReport {
+    pub runs: u32,
+    /// Percentage of runs that finished the craft without running out of durability or cp
+    pub success_percent: f32,
+    /// Quality over every run, failed runs count as 0 quality
+    pub min_quality: i32,
+    pub median_quality: i32,
+    pub max_quality: i32,
+    /// Number of runs that ended with each HQ%, indexed 0 to 100. Failed runs count as 0%
+    pub hq_percent_distribution: Vec<u32>,
+}
+
+impl MonteCarloReport {
+    /// Average HQ% over every run
+    pub fn average_hq_percent(&self) -> f32 {
+        let total: u32 = self
+            .hq_percent_distribution
+            .iter()
+            .enumerate()
+            .map(|(hq, count)| hq as u32 * count)
+            .sum();
+        total as f32 / self.runs as f32
+    }
+}
+
+/// How a rotation did over the trials of the robust fitness
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct RobustScore {
+    /// Fraction of trials that finished the craft, 0 to 1
+    pub success_rate: f32,
+    /// Quality at the chosen percentile, failed trials count as 0
+    pub quality: i32,
+    pub hq_percent: u32,
+}
+
+/// Runs the sequence through `trials` seeded trials and takes the quality at `hqPercentile`.
+/// HQ% only goes up with quality, so the trial at the quality percentile is also the one at the HQ% percentile.
+pub(crate) fn robust_score(
+    synth: &Synth,
+    actions: &[Action],
+    robust: &RobustFitness,
+) -> RobustScore {
+    let trials = robust.trials.max(1);
+    let max_quality = synth.recipe.max_quality;
+    let mut seeds = ChaCha8Rng::seed_from_u64(robust.seed);
+    let mut successes = 0;
+    let mut qualities: Vec<i32> = (0..trials)
+        .map(|_| {
+            let state = run_sequence(synth, actions, seeds.gen());
+            let violations = state.check_violations();
+            if violations.progress_ok && violations.durability_ok && violations.cp_ok {
+                successes += 1;
+                state.quality_state
+            } else {
+                0
+            }
+        })
+        .collect();
+    qualities.sort_unstable();
+    let percentile = robust.hq_percentile.clamp(0.0, 100.0) / 100.0;
+    let quality = qualities[(percentile * (trials - 1) as f32).round() as usize];
+    RobustScore {
+        success_rate: successes as f32 / trials as f32,
+        quality,
+        hq_percent: hq_percent(quality, max_quality),
+    }
+}
+
+/// Runs the sequence once, stopping early if the craft finishes or breaks
+fn run_sequence<'a>(synth: &'a Synth, actions: &[Action], seed: u64) -> State<'a> {
+    let mut condition = SimulationCondition::new_monte_carlo(seed);
+    let mut state = State::from(synth);
+    for action in actions {
+        state = state.add_action(*action, &mut condition);
+        if state.progress_state >= synth.recipe.difficulty as i32 || state.durability_state <= 0 {
+            break;
+        }
+    }
+    state
+}
+
+/// Runs `maxMontecarloRuns` trials of the sequence. Each trial gets its own seed drawn from `seed`,
+/// so the report is reproducible.
+pub fn monte_carlo_sim(synth: &Synth, actions: &[Action], seed: u64) -> MonteCarloReport {
+    let runs = synth.max_montecarlo_runs.max(1);
+    let mut seeds = ChaCha8Rng::seed_from_u64(seed);
+    let mut successes = 0;
+    let mut qualities = Vec::with_capacity(runs as usize);
+    let mut hq_percent_distribution = vec![0; 101];
+    for _ in 0..runs {
+        let state = run_sequence(synth, actions, seeds.gen());
+        let violations = state.check_violations();
+        let success = violations.progress_ok && violations.durability_ok && violations.cp_ok;
+        let (quality, hq_percent) = if success {
+            successes += 1;
+            let quality = state.quality_state;
+            (quality, hq_percent(quality, synth.recipe.max_quality))
+        } else {
+            (0, 0)
+        };
+        hq_percent_distribution[hq_percent as usize] += 1;
+        qualities.push(quality);
+    }
+    qualities.sort_unstable();
+    MonteCarloReport {
+        runs,
+        success_percent: successes as f32 / runs as f32 * 100.0,
+        min_quality: qualities[0],
+        median_quality: qualities[qualities.len() / 2],
+        max_quality: qualities[qualities.len() - 1],
+        hq_percent_distribution,
+    }
+}
+
+#[wasm_bindgen]
+pub fn monte_carlo_wasm(synth: JsValue, actions: JsValue, seed: u32) -> JsValue {
+    console_error_panic_hook::set_once();
+    let synth: Synth = serde_wasm_bindgen::from_value(synth).unwrap();
+    let actions: Vec<Action> = serde_wasm_bindgen::from_value(actions).unwrap();
+    serde_wasm_bindgen::to_value(&monte_carlo_sim(&synth, &actions, seed as u64)).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::actions::Action;
+    use crate::monte_carlo::{monte_carlo_sim, robust_score};
+    use crate::xiv_model::{RobustFitness, Synth};
+
+    const CRAFTER_SYNTH: &str = r#"{"crafter":{"level":78,"craftsmanship":863,"control":877,"cp":412,"actions":["muscleMemory","reflect","basicSynth2","carefulSynthesis","groundwork","intensiveSynthesis","delicateSynthesis","basicTouch","standardTouch","byregotsBlessing","preciseTouch","prudentTouch","preparatoryTouch","tricksOfTheTrade","mastersMend","wasteNot","wasteNot2","veneration","greatStrides","innovation","finalAppraisal","observe"]},"recipe":{"cls":"Weaver","level":390,"difficulty":1195,"durability":60,"startQuality":0,"safetyMargin":0,"maxQuality":3010,"baseLevel":71,"progressDivider":101,"progressModifier":100,"qualityDivider":81,"qualityModifier":100,"suggestedControl":1220,"suggestedCraftsmanship":1320,"name":"Custom Gathering Tool Components"},"sequence":[],"algorithm":"eaComplex","maxTricksUses":0,"maxMontecarloRuns":400,"reliabilityPercent":100,"useConditions":false,"maxLength":50,"solver":{"algorithm":"eaComplex","penaltyWeight":10000,"population":200000,"subPopulations":10,"solveForCompletion":false,"remainderCPFitnessValue":10,"remainderDurFitnessValue":100,"maxStagnationCounter":25,"generations":2000},"debug":true}"#;
+
+    fn synth() -> Synth {
+        let mut synth: Synth = serde_json::from_str(CRAFTER_SYNTH).unwrap();
+        synth.crafter.actions.push(Action::HastyTouch);
+        synth
+    }
+
+    const SEQUENCE: [Action; 9] = [
+        Action::MuscleMemory,
+        Action::Veneration,
+        Action::Groundwork,
+        Action::HastyTouch,
+        Action::HastyTouch,
+        Action::MastersMend,
+        Action::HastyTouch,
+        Action::Veneration,
+        Action::Groundwork,
+    ];
+
+    #[test]
+    fn seeded_runs_are_repeatable() {
+        let synth = synth();
+        let first = monte_carlo_sim(&synth, &SEQUENCE, 42);
+        let second = monte_carlo_sim(&synth, &SEQUENCE, 42);
+        assert_eq!(first, second);
+        assert_eq!(first.runs, 400);
+        assert_eq!(first.hq_percent_distribution.iter().sum::<u32>(), 400);
+        // pinned so the run seeds come out the same on wasm32 and native
+        assert_eq!(
+            (first.min_quality, first.median_quality, first.max_quality),
+            (0, 300, 1055)
+        );
+    }
+
+    #[test]
+    fn samples_success_rolls() {
+        let synth = synth();
+        let report = monte_carlo_sim(&synth, &SEQUENCE, 7);
+        // progress actions are all 100% so every run should finish
+        assert_eq!(report.success_percent, 100.0);
+        // hasty touch only lands 60% of the time, so the quality should spread out
+        assert!(report.min_quality < report.max_quality);
+        assert!(report.min_quality <= report.median_quality);
+        assert!(report.median_quality <= report.max_quality);
+        assert!(report.average_hq_percent() > 0.0);
+    }
+
+    #[test]
+    fn failed_craft() {
+        let synth = synth();
+        let report = monte_carlo_sim(&synth, &[Action::BasicTouch], 1);
+        assert_eq!(report.success_percent, 0.0);
+        assert_eq!(report.hq_percent_distribution[0], report.runs);
+        assert_eq!(report.max_quality, 0);
+    }
+
+    #[test]
+    fn robust_percentile() {
+        let synth = synth();
+        let robust = RobustFitness::default();
+        let score = robust_score(&synth, &SEQUENCE, &robust);
+        assert_eq!(score, robust_score(&synth, &SEQUENCE, &robust));
+        // pinned so the trial seeds come out the same everywhere
+        let median = robust_score(
+            &synth,
+            &SEQUENCE,
+            &RobustFitness {
+                hq_percentile: 50.0,
+                ..robust.clone()
+            },
+        );
+        assert_eq!((score.quality, median.quality), (0, 300));
+        assert_eq!(score.success_rate, 1.0);
+        // a low percentile is held back by the runs where hasty touch misses
+        let best = robust_score(
+            &synth,
+            &SEQUENCE,
+            &RobustFitness {
+                hq_percentile: 100.0,
+                ..robust.clone()
+            },
+        );
+        assert!(score.quality < best.quality);
+        assert!(score.hq_percent <= best.hq_percent);
+
+        let failed = robust_score(&synth, &[Action::BasicTouch], &robust);
+        assert_eq!(failed.success_rate, 0.0);
+        assert_eq!(failed.quality, 0);
+    }
+}
@@ -2,16 +2,56 @@ use genevo::genetic::Genotype;
 use genevo::operator::{GeneticOperator, MutationOp};
 use genevo::prelude::Rng;
 use genevo::random::SampleUniform;
+use serde::{Deserialize, Serialize};
 
 pub(crate) trait IndexedSizedContainer<T> {
     fn insert(&mut self, index: usize, value: T);
     fn remove(&mut self, index: usize);
     fn replace(&mut self, index: usize, value: T);
+    fn get(&self, index: usize) -> T;
     fn len(&self) -> usize;
 }
 
+/// Relative odds of each mutation operator, an operator with a weight of 0 is never used
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct MutationWeights {
+    /// Remove a random gene
+    pub remove: u32,
+    /// Overwrite a random gene with any value
+    pub replace: u32,
+    /// Insert a random value
+    pub insert: u32,
+    /// Swap two genes
+    pub swap: u32,
+    /// Move a short block of genes somewhere else
+    pub move_block: u32,
+    /// Copy a short block of genes and insert it somewhere else
+    pub duplicate: u32,
+    /// Overwrite a gene with another action of the same category (progress, touch, buff, repair)
+    pub same_category: u32,
+}
+
+impl Default for MutationWeights {
+    /// The 1:4:1 split of remove, replace and insert the mutator always used, the other operators have to be turned on
+    fn default() -> Self {
+        Self {
+            remove: 1,
+            replace: 4,
+            insert: 1,
+            swap: 0,
+            move_block: 0,
+            duplicate: 0,
+            same_category: 0,
+        }
+    }
+}
+
+/// Longest block moved or duplicated at once
+const MAX_BLOCK_LENGTH: usize = 3;
+
 /// Mutates the length a container (in most cases a vector) as well as the values inside it
-/// Will insert, remove, and replace values, weighted towards replace in most cases.
+/// Will insert, remove, replace and rearrange values, picking each operator by its weight.
 /// GrowableContainer must be implemented for the Genome type.
 #[derive(Clone, Debug)]
 pub(crate) struct SizeAndValueMutator<T> {
@@ -25,6 +65,9 @@ pub(crate) struct SizeAndValueMutator<T> {
     max_length: usize,
     /// Number of operations that the mutator will preform, will be at least once.
     mutation_percent: f32,
+    weights: MutationWeights,
+    /// Values that can stand in for each other in a same category replace
+    categories: Vec<Vec<T>>,
 }
 
 impl<T: Copy> SizeAndValueMutator<T> {
@@ -41,8 +84,21 @@ impl<T: Copy> SizeAndValueMutator<T> {
             min_length,
             max_length,
             mutation_percent,
+            weights: MutationWeights::default(),
+            categories: vec![],
         }
     }
+
+    pub fn with_weights(mut self, weights: MutationWeights) -> Self {
+        self.weights = weights;
+        self
+    }
+
+    /// Groups of values for the same category replace, values not in any group are left alone by it
+    pub fn with_categories(mut self, categories: Vec<Vec<T>>) -> Self {
+        self.categories = categories;
+        self
+    }
 }
 
 impl<T: Clone> GeneticOperator for SizeAndValueMutator<T> {
@@ -51,6 +107,58 @@ impl<T: Clone> GeneticOperator for SizeAndValueMutator<T> {
     }
 }
 
+impl<T: SampleUniform + PartialOrd + Copy> SizeAndValueMutator<T> {
+    /// Removes a block of up to [MAX_BLOCK_LENGTH] genes and inserts it somewhere else
+    fn move_block<G: IndexedSizedContainer<T>, R: Rng + Sized>(&self, genome: &mut G, rng: &mut R) {
+        let length = genome.len();
+        if length < 2 {
+            return;
+        }
+        let block_length = rng.gen_range(1..=MAX_BLOCK_LENGTH.min(length - 1));
+        let start = rng.gen_range(0..=length - block_length);
+        let block: Vec<T> = (start..start + block_length)
+            .map(|i| genome.get(i))
+            .collect();
+        for _ in 0..block_length {
+            genome.remove(start);
+        }
+        let destination = rng.gen_range(0..=genome.len());
+        for (offset, value) in block.into_iter().enumerate() {
+            genome.insert(destination + offset, value);
+        }
+    }
+
+    /// Copies a block of up to [MAX_BLOCK_LENGTH] genes and inserts the copy somewhere else
+    fn duplicate<G: IndexedSizedContainer<T>, R: Rng + Sized>(&self, genome: &mut G, rng: &mut R) {
+        let length = genome.len();
+        let space = self.max_length.saturating_sub(length).min(length);
+        if space == 0 {
+            return;
+        }
+        let block_length = rng.gen_range(1..=MAX_BLOCK_LENGTH.min(space));
+        let start = rng.gen_range(0..=length - block_length);
+        let block: Vec<T> = (start..start + block_length)
+            .map(|i| genome.get(i))
+            .collect();
+        let destination = rng.gen_range(0..=length);
+        for (offset, value) in block.into_iter().enumerate() {
+            genome.insert(destination + offset, value);
+        }
+    }
+
+    fn replace_same_category<G: IndexedSizedContainer<T>, R: Rng + Sized>(
+        &self,
+        genome: &mut G,
+        rng: &mut R,
+    ) {
+        let index = rng.gen_range(0..genome.len());
+        let value = genome.get(index);
+        if let Some(category) = self.categories.iter().find(|c| c.contains(&value)) {
+            genome.replace(index, category[rng.gen_range(0..category.len())]);
+        }
+    }
+}
+
 impl<T: SampleUniform + PartialOrd + Copy, G: IndexedSizedContainer<T> + Genotype> MutationOp<G>
     for SizeAndValueMutator<T>
 {
@@ -62,32 +170,174 @@ impl<T: SampleUniform + PartialOrd + Copy, G: IndexedSizedContainer<T> + Genotyp
 
         let mutation_counter =
             (genome.len() as f32 * rng.gen_range(0.0..self.mutation_percent)) as usize;
+        let weights = &self.weights;
+        let ops = [
+            weights.remove,
+            weights.replace,
+            weights.insert,
+            weights.swap,
+            weights.move_block,
+            weights.duplicate,
+            weights.same_category,
+        ];
+        let total: u32 = ops.iter().sum();
+        if total == 0 || genome.len() == 0 {
+            return genome;
+        }
         for _ in 0..=mutation_counter {
-            // 0 = remove item,
-            // 1, 2, 3 = mutate items,
-            // 1 = insert items
-            // tbd: how could I allow the consumer to weight this?
-            let mutation_op = rng.gen_range(0..=5);
-            if mutation_op == 0 {
-                let length = genome.len();
-                if length > self.min_length {
-                    genome.remove(rng.gen_range(0..length))
+            // walk the weights until the roll lands in one of them
+            let mut roll = rng.gen_range(0..total);
+            let mutation_op = ops
+                .iter()
+                .position(|weight| {
+                    if roll < *weight {
+                        true
+                    } else {
+                        roll -= weight;
+                        false
+                    }
+                })
+                .unwrap_or(1);
+            let length = genome.len();
+            match mutation_op {
+                0 => {
+                    if length > self.min_length {
+                        genome.remove(rng.gen_range(0..length))
+                    }
+                }
+                1 => {
+                    // choose a random index to mutate
+                    let mutation_index = rng.gen_range(0..length);
+                    // now overwrite that index with a random value
+                    let random_value = rng.gen_range(self.min_value..=self.max_value);
+                    genome.replace(mutation_index, random_value);
                 }
-            } else if (1..5).contains(&mutation_op) {
-                // choose a random index to mutate
-                let mutation_index = rng.gen_range(0..genome.len());
-                // now overwrite that index with a random value
-                let random_value = rng.gen_range(self.min_value..=self.max_value);
-                genome.replace(mutation_index, random_value);
-            } else if mutation_op == 5 {
-                let length = genome.len();
-                if length < self.max_length {
-                    let index = rng.gen_range(0..length);
-                    let value = rng.gen_range(self.min_value..=self.max_value);
-                    genome.insert(index, value);
+                2 => {
+                    if length < self.max_length {
+                        let index = rng.gen_range(0..length);
+                        let value = rng.gen_range(self.min_value..=self.max_value);
+                        genome.insert(index, value);
+                    }
                 }
+                3 => {
+                    if length > 1 {
+                        // draw two different indices, so a swap never picks the same gene twice
+                        let a = rng.gen_range(0..length);
+                        let mut b = rng.gen_range(0..length - 1);
+                        if b >= a {
+                            b += 1;
+                        }
+                        let value = genome.get(a);
+                        genome.replace(a, genome.get(b));
+                        genome.replace(b, value);
+                    }
+                }
+                4 => self.move_block(&mut genome, rng),
+                5 => self.duplicate(&mut genome, rng),
+                _ => self.replace_same_category(&mut genome, rng),
             }
         }
         genome
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::mutator::{MutationWeights, SizeAndValueMutator};
+    use crate::simulator::CrafterActions;
+    use genevo::operator::MutationOp;
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+    use smallvec::SmallVec;
+
+    /// Every weight set to 0, so a test can switch on a single operator
+    fn only_weights() -> MutationWeights {
+        MutationWeights {
+            remove: 0,
+            replace: 0,
+            insert: 0,
+            swap: 0,
+            move_block: 0,
+            duplicate: 0,
+            same_category: 0,
+        }
+    }
+
+    fn only(weights: MutationWeights) -> SizeAndValueMutator<u8> {
+        SizeAndValueMutator::new(0, 9, 1, 50, 0.3).with_weights(weights)
+    }
+
+    fn sorted(genome: &CrafterActions) -> Vec<u8> {
+        let mut values = genome.to_vec();
+        values.sort_unstable();
+        values
+    }
+
+    #[test]
+    fn rearranging_keeps_genes() {
+        let genome: CrafterActions = SmallVec::from_slice(&[0, 1, 2, 3, 4, 5, 6, 7]);
+        let mut rng = SmallRng::seed_from_u64(3);
+        for weights in [
+            MutationWeights {
+                swap: 1,
+                ..only_weights()
+            },
+            MutationWeights {
+                move_block: 1,
+                ..only_weights()
+            },
+        ] {
+            let mutator = only(weights);
+            for _ in 0..50 {
+                let mutated = mutator.mutate(genome.clone(), &mut rng);
+                assert_eq!(sorted(&mutated), sorted(&genome));
+            }
+        }
+    }
+
+    #[test]
+    fn swap_moves_genes() {
+        // short enough for a single mutation per call
+        let genome: CrafterActions = SmallVec::from_slice(&[0, 1]);
+        let mutator = only(MutationWeights {
+            swap: 1,
+            ..only_weights()
+        });
+        let mut rng = SmallRng::seed_from_u64(3);
+        for _ in 0..50 {
+            let mutated = mutator.mutate(genome.clone(), &mut rng);
+            assert_eq!(mutated.to_vec(), vec![1, 0]);
+        }
+    }
+
+    #[test]
+    fn duplicate_grows() {
+        let genome: CrafterActions = SmallVec::from_slice(&[0, 1, 2, 3]);
+        let mutator = only(MutationWeights {
+            duplicate: 1,
+            ..only_weights()
+        });
+        let mut rng = SmallRng::seed_from_u64(3);
+        for _ in 0..50 {
+            let mutated = mutator.mutate(genome.clone(), &mut rng);
+            assert!(mutated.len() > genome.len());
+            assert!(mutated.iter().all(|v| genome.contains(v)));
+        }
+    }
+
+    #[test]
+    fn same_category_replace() {
+        let genome: CrafterActions = SmallVec::from_slice(&[0, 1, 5, 6]);
+        let mutator = only(MutationWeights {
+            same_category: 1,
+            ..only_weights()
+        })
+        .with_categories(vec![vec![0, 1, 2], vec![5, 6, 7]]);
+        let mut rng = SmallRng::seed_from_u64(3);
+        for _ in 0..50 {
+            let mutated = mutator.mutate(genome.clone(), &mut rng);
+            assert!(mutated[..2].iter().all(|v| *v <= 2));
+            assert!(mutated[2..].iter().all(|v| *v >= 5));
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use crate::actions::Action;
+use crate::hq::hq_percent;
+use crate::simulator::{CalcState, CrafterActions};
+use crate::xiv_model::Synth;
+use serde::{Deserialize, Serialize};
+
+/// Most rotations the archive holds, past this the ones in the most crowded part of the front are dropped
+const MAX_ARCHIVE_SIZE: usize = 64;
+
+/// A rotation that finishes the craft and the objectives it is compared on
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ParetoPoint {
+    pub sequence: Vec<Action>,
+    /// Quality, capped at the recipe's max quality
+    pub quality: i32,
+    pub steps: u32,
+    /// CP left over at the end of the craft
+    pub cp: i32,
+    /// Durability left over at the end of the craft
+    pub durability: i32,
+    pub hq_percent: f32,
+}
+
+impl ParetoPoint {
+    /// True when `self` is at least as good as `other` in every objective and better in one
+    fn dominates(&self, other: &ParetoPoint) -> bool {
+        let at_least = self.quality >= other.quality
+            && self.steps <= other.steps
+            && self.cp >= other.cp
+            && self.durability >= other.durability;
+        at_least
+            && (self.quality > other.quality
+                || self.steps < other.steps
+                || self.cp > other.cp
+                || self.durability > other.durability)
+    }
+
+    /// Same objectives, the archive only needs to keep one of them
+    fn same_objectives(&self, other: &ParetoPoint) -> bool {
+        self.quality == other.quality
+            && self.steps == other.steps
+            && self.cp == other.cp
+            && self.durability == other.durability
+    }
+}
+
+/// The non dominated rotations seen so far over quality, step count, leftover CP and leftover durability.
+/// Only rotations that finish the craft without running out of CP or durability are kept.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ParetoArchive {
+    points: Vec<ParetoPoint>,
+}
+
+impl ParetoArchive {
+    /// Adds the rotation if nothing in the archive dominates it, dropping what it dominates.
+    /// Returns true if the archive changed.
+    pub(crate) fn insert(&mut self, synth: &Synth, genome: &CrafterActions) -> bool {
+        // this runs on every individual, so the sequence is only built once the point is kept
+        let state = genome.calculate_final_state(synth, &mut None);
+        let violations = state.check_violations();
+        if !(violations.progress_ok && violations.cp_ok && violations.durability_ok) {
+            return false;
+        }
+        let max_quality = synth.recipe.max_quality;
+        let quality = state.quality_state.min(max_quality as i32);
+        let mut point = ParetoPoint {
+            sequence: vec![],
+            quality,
+            steps: state.actions_used() as u32,
+            cp: state.cp_state,
+            durability: state.durability_state,
+            hq_percent: hq_percent(quality, max_quality) as f32,
+        };
+        if self
+            .points
+            .iter()
+            .any(|p| p.dominates(&point) || p.same_objectives(&point))
+        {
+            return false;
+        }
+        self.points.retain(|p| !point.dominates(p));
+        let mut sequence = genome.get_actions_list(synth);
+        sequence.truncate(state.actions_used());
+        point.sequence = sequence;
+        self.points.push(point);
+        if self.points.len() > MAX_ARCHIVE_SIZE {
+            self.drop_most_crowded();
+        }
+        true
+    }
+
+    /// Removes the point with the smallest crowding distance, the sum over every objective of the gap
+    /// between its neighbours. The best and worst point of each objective are never removed.
+    fn drop_most_crowded(&mut self) {
+        let count = self.points.len();
+        let objectives: [fn(&ParetoPoint) -> f32; 4] = [
+            |p| p.quality as f32,
+            |p| p.steps as f32,
+            |p| p.cp as f32,
+            |p| p.durability as f32,
+        ];
+        let mut distances = vec![0.0; count];
+        let mut order: Vec<usize> = (0..count).collect();
+        for objective in objectives {
+            let value = |i: usize| objective(&self.points[i]);
+            order.sort_by(|a, b| value(*a).total_cmp(&value(*b)));
+            let range = value(order[count - 1]) - value(order[0]);
+            // an objective every point shares has no extremes to keep
+            if range > 0.0 {
+                distances[order[0]] = f32::INFINITY;
+                distances[order[count - 1]] = f32::INFINITY;
+                for window in order.windows(3) {
+                    distances[window[1]] += (value(window[2]) - value(window[0])) / range;
+                }
+            }
+        }
+        if let Some(crowded) = (0..count).min_by(|a, b| distances[*a].total_cmp(&distances[*b])) {
+            self.points.swap_remove(crowded);
+        }
+    }
+
+    /// Highest quality first, ties broken by the shortest rotation
+    fn sort(&mut self) {
+        self.points
+            .sort_by(|a, b| b.quality.cmp(&a.quality).then(a.steps.cmp(&b.steps)));
+    }
+
+    pub(crate) fn into_front(mut self) -> Vec<ParetoPoint> {
+        self.sort();
+        self.points
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::actions::Action;
+    use crate::genome::genome_from_actions;
+    use crate::pareto::{ParetoArchive, ParetoPoint, MAX_ARCHIVE_SIZE};
+    use crate::xiv_model::Synth;
+
+    const CRAFTER_SYNTH: &str = r#"{"crafter":{"level":78,"craftsmanship":863,"control":877,"cp":412,"actions":["muscleMemory","reflect","basicSynth2","carefulSynthesis","groundwork","intensiveSynthesis","delicateSynthesis","basicTouch","standardTouch","byregotsBlessing","preciseTouch","prudentTouch","preparatoryTouch","tricksOfTheTrade","mastersMend","wasteNot","wasteNot2","veneration","greatStrides","innovation","finalAppraisal","observe"]},"recipe":{"cls":"Weaver","level":390,"difficulty":1195,"durability":60,"startQuality":0,"safetyMargin":0,"maxQuality":3010,"baseLevel":71,"progressDivider":101,"progressModifier":100,"qualityDivider":81,"qualityModifier":100,"suggestedControl":1220,"suggestedCraftsmanship":1320,"name":"Custom Gathering Tool Components"},"sequence":[],"algorithm":"eaComplex","maxTricksUses":0,"maxMontecarloRuns":400,"reliabilityPercent":100,"useConditions":false,"maxLength":50,"solver":{"algorithm":"eaComplex","penaltyWeight":10000,"population":200000,"subPopulations":10,"solveForCompletion":false,"remainderCPFitnessValue":10,"remainderDurFitnessValue":100,"maxStagnationCounter":25,"generations":2000},"debug":true}"#;
+
+    fn point(quality: i32, steps: u32) -> ParetoPoint {
+        ParetoPoint {
+            sequence: vec![],
+            quality,
+            steps,
+            cp: 0,
+            durability: 0,
+            hq_percent: 0.0,
+        }
+    }
+
+    #[test]
+    fn keeps_only_non_dominated() {
+        let synth: Synth = serde_json::from_str(CRAFTER_SYNTH).unwrap();
+        let mut archive = ParetoArchive::default();
+        let short = [
+            Action::MuscleMemory,
+            Action::Veneration,
+            Action::Groundwork,
+            Action::Groundwork,
+        ];
+        let with_quality = [
+            Action::MuscleMemory,
+            Action::Veneration,
+            Action::Groundwork,
+            Action::BasicTouch,
+            Action::Groundwork,
+        ];
+        // an observe costs a step and cp without adding anything
+        let wasteful = [
+            Action::MuscleMemory,
+            Action::Veneration,
+            Action::Observe,
+            Action::Groundwork,
+            Action::Groundwork,
+        ];
+        // never finishes the craft
+        let unfinished = [Action::Reflect, Action::BasicTouch];
+
+        assert!(archive.insert(&synth, &genome_from_actions(&synth, &short)));
+        assert!(archive.insert(&synth, &genome_from_actions(&synth, &with_quality)));
+        assert!(!archive.insert(&synth, &genome_from_actions(&synth, &wasteful)));
+        assert!(!archive.insert(&synth, &genome_from_actions(&synth, &unfinished)));
+        assert!(!archive.insert(&synth, &genome_from_actions(&synth, &short)));
+
+        let front = archive.into_front();
+        assert_eq!(front.len(), 2);
+        assert_eq!(front[0].sequence, with_quality.to_vec());
+        assert!(front[0].quality > front[1].quality);
+        assert!(front[0].steps > front[1].steps);
+    }
+
+    #[test]
+    fn counts_careful_observation() {
+        let mut synth: Synth = serde_json::from_str(CRAFTER_SYNTH).unwrap();
+        synth.crafter.specialist = true;
+        synth.crafter.actions.push(Action::CarefulObservation);
+        let sequence = [
+            Action::CarefulObservation,
+            Action::MuscleMemory,
+            Action::Veneration,
+            Action::Groundwork,
+            Action::Groundwork,
+        ];
+        let mut archive = ParetoArchive::default();
+        assert!(archive.insert(&synth, &genome_from_actions(&synth, &sequence)));
+        let front = archive.into_front();
+        // careful observation doesn't take a step, but it's still one of the actions to press
+        assert_eq!(front[0].sequence, sequence.to_vec());
+        assert_eq!(front[0].steps, sequence.len() as u32);
+    }
+
+    #[test]
+    fn drops_the_most_crowded_point() {
+        // every extra step buys 5 quality, so none of these dominate each other
+        let mut archive = ParetoArchive {
+            points: (0..MAX_ARCHIVE_SIZE as i32)
+                .map(|i| point(i * 10, i as u32 * 2))
+                .collect(),
+        };
+        // squeezed in between two points that are already on the front
+        archive.points.push(point(201, 41));
+        archive.drop_most_crowded();
+        assert_eq!(archive.points.len(), MAX_ARCHIVE_SIZE);
+        assert!(!archive.points.contains(&point(201, 41)));
+        assert!(archive.points.contains(&point(0, 0)));
+        assert!(archive.points.contains(&point(630, 126)));
+    }
+}
@@ -0,0 +1,328 @@
+use crate::xiv_model::{CraftingClass, LocalizedName, Recipe};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::JsValue;
+
+/// One recipe as it's stored in `app/data/recipedb/<Class>.json`
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct RecipeEntry {
+    name: LocalizedName,
+    base_level: u32,
+    level: u32,
+    difficulty: u32,
+    durability: u32,
+    max_quality: u32,
+    suggested_craftsmanship: u32,
+    suggested_control: u32,
+    progress_divider: f32,
+    progress_modifier: Option<u32>,
+    quality_divider: f32,
+    quality_modifier: Option<u32>,
+    stars: Option<u32>,
+}
+
+/// A recipe from the database along with what it's called and who crafts it
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RecipeInfo {
+    pub class: CraftingClass,
+    pub name: LocalizedName,
+    #[serde(flatten)]
+    pub recipe: Recipe,
+}
+
+impl From<(CraftingClass, RecipeEntry)> for RecipeInfo {
+    fn from((class, entry): (CraftingClass, RecipeEntry)) -> Self {
+        Self {
+            class,
+            name: entry.name,
+            recipe: Recipe {
+                cls: Some(class),
+                base_level: entry.base_level,
+                level: entry.level,
+                difficulty: entry.difficulty,
+                durability: entry.durability,
+                safety_margin: 0,
+                start_quality: 0,
+                max_quality: entry.max_quality,
+                suggested_craftsmanship: entry.suggested_craftsmanship,
+                suggested_control: entry.suggested_control,
+                progress_divider: entry.progress_divider,
+                progress_modifier: entry.progress_modifier,
+                quality_divider: entry.quality_divider,
+                quality_modifier: entry.quality_modifier,
+                stars: entry.stars,
+                quality_tiers: vec![],
+            },
+        }
+    }
+}
+
+/// Filters for [RecipeDb::query], anything left out matches every recipe
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RecipeQuery {
+    /// Fuzzy matched against the name in every language, results come back best match first
+    pub name: Option<String>,
+    pub class: Option<CraftingClass>,
+    /// Lowest and highest `baseLevel`, the crafter level the recipe is listed under
+    pub min_level: Option<u32>,
+    pub max_level: Option<u32>,
+    /// Most results to return, 0 for all of them
+    pub limit: usize,
+}
+
+/// How far apart two strings are in single character edits
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == cb {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+/// Lower is a better match, `None` when the name is too far off.
+/// Exact matches come first, then prefixes, then names containing the query,
+/// then names or words in them a couple of typos away.
+fn match_score(query: &str, name: &str) -> Option<usize> {
+    let name = name.to_lowercase();
+    if name.is_empty() {
+        return None;
+    }
+    if name == query {
+        return Some(0);
+    }
+    if name.starts_with(query) {
+        return Some(1);
+    }
+    if name.contains(query) {
+        return Some(2);
+    }
+    let query: Vec<char> = query.chars().collect();
+    let allowed = (query.len() / 4).max(1);
+    std::iter::once(name.as_str())
+        .chain(name.split_whitespace())
+        .map(|candidate| edit_distance(&query, &candidate.chars().collect::<Vec<_>>()))
+        .min()
+        .filter(|distance| *distance <= allowed)
+        .map(|distance| 2 + distance)
+}
+
+/// Every recipe in `app/data/recipedb`, searchable by name, class and level
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default)]
+pub struct RecipeDb {
+    recipes: Vec<RecipeInfo>,
+}
+
+impl RecipeDb {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the recipes of one class from the contents of its `<Class>.json`
+    pub fn add_class(&mut self, class: CraftingClass, json: &str) -> Result<(), serde_json::Error> {
+        let entries: Vec<RecipeEntry> = serde_json::from_str(json)?;
+        self.recipes.extend(
+            entries
+                .into_iter()
+                .map(|entry| RecipeInfo::from((class, entry))),
+        );
+        Ok(())
+    }
+
+    /// Loads every `<Class>.json` in the directory, the class comes from the file name
+    pub fn load_dir(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let mut db = Self::new();
+        let mut files: Vec<_> = std::fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<_, _>>()?;
+        files.sort();
+        for file in files {
+            if file.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            let class = file
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or_default()
+                .parse()
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            db.add_class(class, &std::fs::read_to_string(&file)?)?;
+        }
+        Ok(db)
+    }
+
+    pub fn recipes(&self) -> &[RecipeInfo] {
+        &self.recipes
+    }
+
+    /// The recipe with this name in any language, ignoring case
+    pub fn by_name(&self, name: &str) -> Option<&RecipeInfo> {
+        let name = name.to_lowercase();
+        self.recipes.iter().find(|info| {
+            info.name
+                .all()
+                .iter()
+                .any(|candidate| candidate.to_lowercase() == name)
+        })
+    }
+
+    pub fn by_class(&self, class: CraftingClass) -> impl Iterator<Item = &RecipeInfo> {
+        self.recipes.iter().filter(move |info| info.class == class)
+    }
+
+    /// Recipes listed from `min_level` to `max_level` inclusive
+    pub fn by_level(&self, min_level: u32, max_level: u32) -> impl Iterator<Item = &RecipeInfo> {
+        self.recipes
+            .iter()
+            .filter(move |info| (min_level..=max_level).contains(&info.recipe.base_level))
+    }
+
+    /// Recipes whose name is close to `name` in any language, best match first
+    pub fn search(&self, name: &str) -> Vec<&RecipeInfo> {
+        self.query(&RecipeQuery {
+            name: Some(name.to_string()),
+            ..Default::default()
+        })
+    }
+
+    /// Recipes matching every filter in the query.
+    /// Sorted by how well the name matches when there is one, otherwise by level like the JS recipe library.
+    pub fn query(&self, query: &RecipeQuery) -> Vec<&RecipeInfo> {
+        let name = query.name.as_ref().map(|name| name.trim().to_lowercase());
+        let mut results: Vec<(usize, &RecipeInfo)> = self
+            .recipes
+            .iter()
+            .filter(|info| {
+                query.class.is_none_or(|class| info.class == class)
+                    && query
+                        .min_level
+                        .is_none_or(|level| info.recipe.base_level >= level)
+                    && query
+                        .max_level
+                        .is_none_or(|level| info.recipe.base_level <= level)
+            })
+            .filter_map(|info| match &name {
+                Some(name) => info
+                    .name
+                    .all()
+                    .iter()
+                    .filter_map(|candidate| match_score(name, candidate))
+                    .min()
+                    .map(|score| (score, info)),
+                None => Some((0, info)),
+            })
+            .collect();
+        results.sort_by(|(a_score, a), (b_score, b)| {
+            a_score
+                .cmp(b_score)
+                .then(a.recipe.level.cmp(&b.recipe.level))
+                .then(a.name.en.cmp(&b.name.en))
+        });
+        if query.limit > 0 {
+            results.truncate(query.limit);
+        }
+        results.into_iter().map(|(_, info)| info).collect()
+    }
+}
+
+#[wasm_bindgen]
+impl RecipeDb {
+    pub fn new_wasm() -> Self {
+        console_error_panic_hook::set_once();
+        Self::new()
+    }
+
+    pub fn add_class_wasm(&mut self, class: &str, json: &str) -> Result<(), JsValue> {
+        let class: CraftingClass = class.parse().map_err(|e: String| JsValue::from_str(&e))?;
+        self.add_class(class, json)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    pub fn query_wasm(&self, query: JsValue) -> JsValue {
+        let query: RecipeQuery = serde_wasm_bindgen::from_value(query).unwrap_or_default();
+        serde_wasm_bindgen::to_value(&self.query(&query)).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::recipedb::{edit_distance, RecipeDb, RecipeQuery};
+    use crate::xiv_model::CraftingClass;
+
+    fn load() -> RecipeDb {
+        RecipeDb::load_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/../app/data/recipedb")).unwrap()
+    }
+
+    #[test]
+    fn loads_every_class() {
+        let db = load();
+        let mut classes: Vec<CraftingClass> = db.recipes().iter().map(|r| r.class).collect();
+        classes.dedup();
+        assert_eq!(classes.len(), 8);
+        assert_eq!(db.by_class(CraftingClass::Weaver).count(), 1571);
+
+        let yarn = db.by_name("hempen yarn").unwrap();
+        assert_eq!(yarn.class, CraftingClass::Weaver);
+        assert_eq!(yarn.recipe.cls, Some(CraftingClass::Weaver));
+        assert_eq!(yarn.recipe.difficulty, 9);
+        assert_eq!(yarn.recipe.progress_modifier, Some(100));
+        // every language finds the same recipe
+        assert_eq!(db.by_name("Hanfgarn").unwrap().name, yarn.name);
+        assert_eq!(db.by_name("草糸").unwrap().name, yarn.name);
+    }
+
+    #[test]
+    fn levels_and_classes() {
+        let db = load();
+        assert!(db.by_level(50, 50).all(|info| info.recipe.base_level == 50));
+        let results = db.query(&RecipeQuery {
+            class: Some(CraftingClass::Culinarian),
+            min_level: Some(10),
+            max_level: Some(20),
+            ..Default::default()
+        });
+        assert!(!results.is_empty());
+        assert!(results
+            .iter()
+            .all(|info| info.class == CraftingClass::Culinarian
+                && (10..=20).contains(&info.recipe.base_level)));
+        assert!(results
+            .windows(2)
+            .all(|pair| pair[0].recipe.level <= pair[1].recipe.level));
+    }
+
+    #[test]
+    fn fuzzy_search() {
+        let db = load();
+        assert_eq!(edit_distance(&['y', 'a', 'r', 'n'], &['y', 'r', 'n']), 1);
+        let results = db.query(&RecipeQuery {
+            name: Some("Hempen Yarn".to_string()),
+            limit: 5,
+            ..Default::default()
+        });
+        assert_eq!(results[0].name.en, "Hempen Yarn");
+        assert!(results.len() <= 5);
+        // a typo still finds it
+        let results = db.query(&RecipeQuery {
+            name: Some("hempin yarn".to_string()),
+            class: Some(CraftingClass::Weaver),
+            ..Default::default()
+        });
+        assert_eq!(results[0].name.en, "Hempen Yarn");
+        assert!(db.search("zzzzzzzzzz").is_empty());
+    }
+}
@@ -0,0 +1,197 @@
+use crate::actions::Action;
+use crate::xiv_model::{Condition, SimulationCondition, State, Synth, Violations};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::JsValue;
+
+/// A buff that is active after a step
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ActiveBuff {
+    pub action: Action,
+    /// Turns left on a countdown buff, `None` for buffs that stay until they are consumed
+    pub remaining_turns: Option<i32>,
+}
+
+/// Public view of the craft after an action has been applied
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StepSnapshot {
+    pub step: u32,
+    /// Action leading to this snapshot, `None` before the first action
+    pub action: Option<Action>,
+    pub progress: i32,
+    pub quality: i32,
+    pub durability: i32,
+    pub cp: i32,
+    pub buffs: Vec<ActiveBuff>,
+    pub inner_quiet: i32,
+    pub condition: Condition,
+    pub violations: Violations,
+}
+
+impl From<&State<'_>> for StepSnapshot {
+    fn from(state: &State<'_>) -> Self {
+        let effects = &state.effects;
+        let buffs = effects
+            .count_downs
+            .iter()
+            .map(|(action, turns)| ActiveBuff {
+                action,
+                remaining_turns: Some(turns as i32),
+            })
+            .chain(
+                effects
+                    .count_ups
+                    .iter()
+                    .filter(|(action, _)| *action != Action::InnerQuiet)
+                    .map(|(action, _)| ActiveBuff {
+                        action,
+                        remaining_turns: None,
+                    }),
+            )
+            .collect();
+        // inner quiet is tracked starting from -1, so shift it to get the number of stacks
+        let inner_quiet = effects
+            .count_ups
+            .get(Action::InnerQuiet)
+            .map(|(_, stacks)| *stacks as i32 + 1)
+            .unwrap_or(0);
+        Self {
+            step: state.step,
+            action: state.action,
+            progress: state.progress_state,
+            quality: state.quality_state,
+            durability: state.durability_state,
+            cp: state.cp_state,
+            buffs,
+            inner_quiet,
+            condition: state.condition,
+            violations: state.check_violations(),
+        }
+    }
+}
+
+/// Step by step simulation of a synth, applying one action at a time.
+/// Uses the same model as the solver, so the snapshots match what the solver scores.
+pub struct Simulation<'a> {
+    state: State<'a>,
+    condition: SimulationCondition,
+}
+
+impl<'a> Simulation<'a> {
+    pub fn new(synth: &'a Synth) -> Self {
+        Self {
+            state: State::from(synth),
+            condition: SimulationCondition::new_sim_condition(synth),
+        }
+    }
+
+    /// Simulation that rolls conditions and action success from a seeded rng,
+    /// the same seed always produces the same craft
+    pub fn monte_carlo(synth: &'a Synth, seed: u64) -> Self {
+        Self {
+            state: State::from(synth),
+            condition: SimulationCondition::new_monte_carlo(seed),
+        }
+    }
+
+    /// Applies a single action and returns the resulting snapshot
+    pub fn apply(&mut self, action: Action) -> StepSnapshot {
+        self.state = self.state.add_action(action, &mut self.condition);
+        self.snapshot()
+    }
+
+    /// Applies every action in order, returning a snapshot for each step
+    pub fn apply_all<I: IntoIterator<Item = Action>>(&mut self, actions: I) -> Vec<StepSnapshot> {
+        actions
+            .into_iter()
+            .map(|action| self.apply(action))
+            .collect()
+    }
+
+    /// Snapshot of the current state without applying anything
+    pub fn snapshot(&self) -> StepSnapshot {
+        (&self.state).into()
+    }
+
+    /// True once progress is complete or the item has broken
+    pub fn is_finished(&self) -> bool {
+        self.state.progress_state >= self.state.synth.recipe.difficulty as i32
+            || self.state.durability_state <= 0
+    }
+}
+
+/// Runs a full sequence, returning the starting snapshot followed by one snapshot per action
+pub fn simulate_sequence(synth: &Synth, actions: &[Action]) -> Vec<StepSnapshot> {
+    let mut simulation = Simulation::new(synth);
+    let mut steps = vec![simulation.snapshot()];
+    steps.extend(simulation.apply_all(actions.iter().copied()));
+    steps
+}
+
+#[wasm_bindgen]
+pub fn simulate_sequence_wasm(synth: JsValue, actions: JsValue) -> JsValue {
+    console_error_panic_hook::set_once();
+    let synth: Synth = serde_wasm_bindgen::from_value(synth).unwrap();
+    let actions: Vec<Action> = serde_wasm_bindgen::from_value(actions).unwrap();
+    serde_wasm_bindgen::to_value(&simulate_sequence(&synth, &actions)).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::actions::Action;
+    use crate::simulation::{simulate_sequence, Simulation};
+    use crate::xiv_model::{Condition, Synth};
+
+    const CRAFTER_SYNTH: &str = r#"{"crafter":{"level":78,"craftsmanship":863,"control":877,"cp":412,"actions":["muscleMemory","reflect","basicSynth2","carefulSynthesis","groundwork","intensiveSynthesis","delicateSynthesis","basicTouch","standardTouch","byregotsBlessing","preciseTouch","prudentTouch","preparatoryTouch","tricksOfTheTrade","mastersMend","wasteNot","wasteNot2","veneration","greatStrides","innovation","finalAppraisal","observe"]},"recipe":{"cls":"Weaver","level":390,"difficulty":1195,"durability":60,"startQuality":0,"safetyMargin":0,"maxQuality":3010,"baseLevel":71,"progressDivider":101,"progressModifier":100,"qualityDivider":81,"qualityModifier":100,"suggestedControl":1220,"suggestedCraftsmanship":1320,"name":"Custom Gathering Tool Components"},"sequence":[],"algorithm":"eaComplex","maxTricksUses":0,"maxMontecarloRuns":400,"reliabilityPercent":100,"useConditions":false,"maxLength":50,"solver":{"algorithm":"eaComplex","penaltyWeight":10000,"population":200000,"subPopulations":10,"solveForCompletion":false,"remainderCPFitnessValue":10,"remainderDurFitnessValue":100,"maxStagnationCounter":25,"generations":2000},"debug":true}"#;
+
+    #[test]
+    fn step_by_step() {
+        let synth: Synth = serde_json::from_str(CRAFTER_SYNTH).unwrap();
+        let mut simulation = Simulation::new(&synth);
+        let start = simulation.snapshot();
+        assert_eq!(start.step, 0);
+        assert_eq!(start.cp, 412);
+        assert_eq!(start.durability, 60);
+        assert_eq!(start.inner_quiet, 0);
+        assert_eq!(start.condition, Condition::Normal);
+
+        let muscle_memory = simulation.apply(Action::MuscleMemory);
+        assert_eq!(muscle_memory.step, 1);
+        assert_eq!(muscle_memory.action, Some(Action::MuscleMemory));
+        assert!(muscle_memory.progress > 0);
+        assert_eq!(muscle_memory.cp, 412 - 6);
+        assert_eq!(muscle_memory.durability, 50);
+        assert!(muscle_memory
+            .buffs
+            .iter()
+            .any(|b| b.action == Action::MuscleMemory && b.remaining_turns == Some(5)));
+
+        simulation.apply(Action::Veneration);
+        let touch = simulation.apply(Action::BasicTouch);
+        assert!(touch.quality > 0);
+        assert_eq!(touch.inner_quiet, 1);
+        let buffs: Vec<Action> = touch.buffs.iter().map(|b| b.action).collect();
+        assert!(buffs.contains(&Action::MuscleMemory));
+        assert!(buffs.contains(&Action::Veneration));
+        assert!(!simulation.is_finished());
+    }
+
+    #[test]
+    fn full_sequence() {
+        let synth: Synth = serde_json::from_str(CRAFTER_SYNTH).unwrap();
+        let actions = [
+            Action::MuscleMemory,
+            Action::Veneration,
+            Action::Groundwork,
+            Action::Groundwork,
+            Action::CarefulSynthesis,
+        ];
+        let steps = simulate_sequence(&synth, &actions);
+        assert_eq!(steps.len(), actions.len() + 1);
+        let last = steps.last().unwrap();
+        assert!(last.violations.progress_ok);
+        assert!(last.progress >= 1195);
+    }
+}
@@ -1,9 +1,19 @@
-use crate::actions::Action;
-use crate::genome::CraftActionGenomeBuilder;
+use crate::actions::{Action, ActionCategory};
+use crate::crossover::RotationCrossBreeder;
+use crate::genome::{genome_from_actions, CraftActionGenomeBuilder};
+use crate::heuristic::heuristic_sequence_builder;
+use crate::hq::hq_percent;
+use crate::local_search::polish;
+use crate::monte_carlo::robust_score;
 use crate::mutator::{IndexedSizedContainer, SizeAndValueMutator};
-use crate::xiv_model::{Condition, SimulationCondition, State, Synth, Violations};
+use crate::pareto::{ParetoArchive, ParetoPoint};
+use crate::solver::ExhaustiveBackend;
+use crate::xiv_model::{Algorithm, Condition, SimulationCondition, State, Synth, Violations};
+use genevo::algorithm::EvaluatedPopulation;
 use genevo::ga::genetic_algorithm;
+use genevo::genetic::Offspring;
 use genevo::operator::prelude::*;
+use genevo::operator::{GeneticOperator, ReinsertionOp};
 use genevo::prelude::*;
 use genevo::prelude::{simulate, FitnessFunction, GenerationLimit, Simulation, SimulationBuilder};
 use genevo::simulation::simulator::Simulator;
@@ -11,8 +21,12 @@ use genevo::simulation::simulator::Simulator;
 use rayon::iter::IntoParallelIterator;
 #[cfg(feature = "thread")]
 use rayon::iter::ParallelIterator;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use serde::{Deserialize, Serialize};
-use std::fmt::Write;
+use std::cmp::Reverse;
+use std::fmt::{Display, Formatter, Write};
+use std::sync::{Arc, Mutex};
 use smallvec::SmallVec;
 use wasm_bindgen::prelude::wasm_bindgen;
 use wasm_bindgen::JsValue;
@@ -46,6 +60,10 @@ impl IndexedSizedContainer<usize> for CrafterActions {
         self[index] = value as u8;
     }
 
+    fn get(&self, index: usize) -> usize {
+        self[index] as usize
+    }
+
     fn len(&self) -> usize {
         self.len()
     }
@@ -64,6 +82,10 @@ impl IndexedSizedContainer<u8> for CrafterActions {
         self[index] = value;
     }
 
+    fn get(&self, index: usize) -> u8 {
+        self[index]
+    }
+
     fn len(&self) -> usize {
         self.len()
     }
@@ -72,7 +94,7 @@ impl IndexedSizedContainer<u8> for CrafterActions {
 impl CalcState for CrafterActions {
     fn calculate_final_state<'a>(&self, synth: &'a Synth, log: &mut Option<String>) -> State<'a> {
         let mut state: State = synth.into();
-        let mut condition = SimulationCondition::new_sim_condition();
+        let mut condition = SimulationCondition::new_sim_condition(synth);
         if let Some(log) = log {
             let _ = writeln!(log, "{}", state);
         }
@@ -112,32 +134,59 @@ impl CalcState for CrafterActions {
     ) -> (State<'a>, Vec<Action>) {
         let actions = self.get_actions_list(synth);
         let state = self.calculate_final_state(synth, log);
-        let (first, _) = actions.split_at(state.step as usize);
+        let (first, _) = actions.split_at(state.actions_used());
         (state, first.to_vec())
     }
 }
 
 impl FitnessFunction<CrafterActions, i32> for Synth {
     fn fitness_of(&self, actions: &CrafterActions) -> i32 {
+        let config = &self.solver_vars.fitness;
         let state = actions.calculate_final_state(self, &mut None);
         let violations = state.check_violations();
-        let penalties = state.calculate_penalties(10000.0) as i32;
+        let penalties = state.calculate_penalties(config.penalty_weight) as i32;
+        let robust = config
+            .robust
+            .as_ref()
+            .map(|robust| robust_score(self, &actions.get_actions_list(self), robust));
+        let quality = match robust {
+            Some(score) => score.quality,
+            None => state.quality_state,
+        };
         let mut fitness = if self.solver_vars.solve_for_completion {
             (state.cp_state * self.solver_vars.remainder_cp_fitness_value)
                 + (state.durability_state * self.solver_vars.remainder_dur_fitness_value)
         } else {
-            state.quality_state.min(self.recipe.max_quality as i32)
+            quality.min(self.target_quality() as i32)
+                + (state.cp_state as f32 * config.leftover_cp_weight
+                    + state.durability_state as f32 * config.leftover_durability_weight)
+                    as i32
         };
         fitness -= penalties;
-        // crafters deliniations cost more, subtract off some to bias towards macros that don't use it even if the user has it selected
-        if state.heart_and_soul_used {
-            fitness -= 1;
+        if let Some(score) = robust {
+            // the expected value pass above still guides the search, every failed trial costs on top of it
+            fitness -= ((1.0 - score.success_rate) * config.penalty_weight) as i32;
         }
+        // only the actions that ran count, the rest of the genome is ignored by the simulation
+        let cost: f32 = actions
+            .iter()
+            .flat_map(|m| self.crafter.actions.get(*m as usize))
+            .take(state.actions_used())
+            .map(|action| config.action_costs.get(*action))
+            .sum();
+        fitness -= cost as i32;
         let safety_margin_factor = 1.0 + self.recipe.safety_margin as f32 * 0.01;
-        if violations.progress_ok
-            && state.quality_state as f32 >= self.recipe.max_quality as f32 * safety_margin_factor
-        {
-            fitness = (fitness as f32 * (1.0 + 4.0 / state.step as f32)) as i32;
+        // a tier is an exact threshold, the safety margin only pads max quality
+        let target = match self.solver_vars.target_tier {
+            Some(_) => self.target_quality() as f32,
+            None => self.recipe.max_quality as f32 * safety_margin_factor,
+        };
+        let finished = match robust {
+            Some(score) => score.success_rate >= 1.0,
+            None => violations.progress_ok,
+        };
+        if finished && quality as f32 >= target {
+            fitness = (fitness as f32 * (1.0 + config.step_bonus / state.step as f32)) as i32;
         }
         fitness
     }
@@ -162,112 +211,382 @@ impl FitnessFunction<CrafterActions, i32> for Synth {
     }
 }
 
-type GeneticSimulator = Simulator<GeneticAlgorithm<CrafterActions, i32, Synth, MaximizeSelector, SinglePointCrossBreeder, SizeAndValueMutator<u8>, ElitistReinserter<CrafterActions, i32, Synth>>, GenerationLimit>;
+type GeneticSimulator = Simulator<GeneticAlgorithm<CrafterActions, i32, Synth, MaximizeSelector, RotationCrossBreeder, SizeAndValueMutator<u8>, MigratingReinserter>, GenerationLimit>;
 
-#[wasm_bindgen]
-pub struct CraftSimulator {
-    pub(crate) generations: u32,
-    // extra copy of our synth.
-    pub(crate) synth: Synth,
-    // oh god this type is so long.
-    pub(crate) sim: GeneticSimulator,
+/// A solver that streams its progress one step at a time.
+/// Each call to [SolverBackend::next_generation] does a chunk of work and reports the best sequence so far,
+/// [SimStep::Success] means the solver is done.
+pub trait SolverBackend {
+    fn next_generation(&mut self) -> SimStep;
 }
 
-impl CraftSimulator {
-    pub fn new(synth: Synth) -> Self {
-        let number_of_available_actions = synth.crafter.actions.len() as u8;
-        let number_of_generations = synth.solver_vars.generations;
+/// Reports the same error every step, for input no solver can run
+struct ErrorBackend(String);
 
-        #[cfg(feature = "wasm-thread")]
-        let population_size = {
-            log(&format!("USING {} cores", rayon::current_num_threads()));
-            synth.solver_vars.population / rayon::current_num_threads() as i32
+impl SolverBackend for ErrorBackend {
+    fn next_generation(&mut self) -> SimStep {
+        SimStep::Error(self.0.clone())
+    }
+}
+
+/// Elitist reinsertion that also lets in the migrants sent from another island.
+/// Migrants join the offspring of the next generation and are kept or dropped like any other child,
+/// so migrating doesn't interrupt the island.
+#[derive(Clone, Debug)]
+pub(crate) struct MigratingReinserter {
+    reinserter: ElitistReinserter<CrafterActions, i32, Synth>,
+    migrants: Arc<Mutex<Vec<CrafterActions>>>,
+}
+
+impl GeneticOperator for MigratingReinserter {
+    fn name() -> String {
+        "Migrating-Elitist-Reinserter".to_string()
+    }
+}
+
+impl ReinsertionOp<CrafterActions, i32> for MigratingReinserter {
+    fn combine<R>(
+        &self,
+        offspring: &mut Offspring<CrafterActions>,
+        population: &EvaluatedPopulation<CrafterActions, i32>,
+        rng: &mut R,
+    ) -> Vec<CrafterActions>
+    where
+        R: Rng + Sized,
+    {
+        offspring.append(&mut self.migrants.lock().unwrap());
+        self.reinserter.combine(offspring, population, rng)
+    }
+}
+
+/// One sub population of the island model.
+/// Islands evolve on their own and only share their best individuals when migrating.
+struct Island {
+    sim: GeneticSimulator,
+    // individuals sent here by the previous island, picked up by the reinserter next generation
+    migrants: Arc<Mutex<Vec<CrafterActions>>>,
+}
+
+impl Island {
+    /// Spreads the selection and mutation settings across the islands so they don't all search the same way,
+    /// a single island uses the settings the solver always used.
+    fn new(
+        synth: &Synth,
+        index: usize,
+        count: usize,
+        population: Population<CrafterActions>,
+        seed: Option<Seed>,
+    ) -> Self {
+        let spread = if count > 1 {
+            index as f64 / (count - 1) as f64
+        } else {
+            0.5
         };
-        #[cfg(not(feature = "wasm-thread"))]
-        let population_size = synth.solver_vars.population;
-        let initial_population: Population<CrafterActions> = build_population()
-            .with_genome_builder(CraftActionGenomeBuilder::new(
-                &synth,
-                1,
-                number_of_available_actions + 1, // 1 is our real first ability
-            ))
-            .of_size(population_size as usize)
-            .uniform_at_random();
-        #[cfg(target_arch = "wasm32")]
-        log(&format!("population_size: {}", population_size));
-        let sim = simulate(
+        let selection_ratio = 0.8 + 0.1 * spread;
+        let mutation_percent = 0.2 + 0.2 * spread as f32;
+        let number_of_available_actions = synth.crafter.actions.len() as u8;
+        let migrants = Arc::new(Mutex::new(vec![]));
+        let builder = simulate(
             genetic_algorithm()
                 .with_evaluation(synth.clone())
-                .with_selection(MaximizeSelector::new(0.85, 18))
-                .with_crossover(SinglePointCrossBreeder::new())
-                .with_mutation(SizeAndValueMutator::new(
-                    0,
-                    number_of_available_actions,
-                    1,
-                    50,
-                    0.3,
-                ))
-                .with_reinsertion(ElitistReinserter::new(synth.clone(), false, 0.85))
-                .with_initial_population(initial_population)
+                .with_selection(MaximizeSelector::new(selection_ratio, 18))
+                .with_crossover(RotationCrossBreeder::new(synth))
+                .with_mutation(
+                    SizeAndValueMutator::new(
+                        0,
+                        number_of_available_actions,
+                        1,
+                        50,
+                        mutation_percent,
+                    )
+                    .with_weights(synth.solver_vars.mutation_weights.clone())
+                    .with_categories(action_categories(synth)),
+                )
+                .with_reinsertion(MigratingReinserter {
+                    reinserter: ElitistReinserter::new(synth.clone(), false, 0.85),
+                    migrants: migrants.clone(),
+                })
+                .with_initial_population(population)
                 .build(),
         )
-        .until(GenerationLimit::new(number_of_generations as u64))
-        .build();
+        .until(GenerationLimit::new(synth.solver_vars.generations as u64));
+        let sim = match seed {
+            Some(seed) => builder.build_with_seed(seed),
+            None => builder.build(),
+        };
+        Self { sim, migrants }
+    }
+}
 
+/// Genome values grouped by the category of the action they stand for
+fn action_categories(synth: &Synth) -> Vec<Vec<u8>> {
+    let mut categories: Vec<(ActionCategory, Vec<u8>)> = vec![];
+    for (index, action) in synth.crafter.actions.iter().enumerate() {
+        let category = action.category();
+        match categories.iter_mut().find(|(c, _)| *c == category) {
+            Some((_, values)) => values.push(index as u8),
+            None => categories.push((category, vec![index as u8])),
+        }
+    }
+    categories.into_iter().map(|(_, values)| values).collect()
+}
+
+/// Next seed for genevo, `None` when the run isn't seeded
+fn next_seed(seeds: &mut Option<ChaCha8Rng>) -> Option<Seed> {
+    seeds.as_mut().map(|rng| rng.gen())
+}
+
+/// Runs the genevo genetic algorithm as an island model, one generation of every island per step.
+/// `solver.subPopulations` sets the number of islands and `solver.population` is split between them.
+/// The islands are stepped one after another on the calling thread, so a step takes about as long as
+/// one generation of the whole population; the `thread` feature only spreads the work inside each island.
+pub(crate) struct GeneticBackend {
+    generations: u32,
+    // extra copy of our synth.
+    synth: Synth,
+    islands: Vec<Island>,
+    best_fitness: Option<i32>,
+    best_genome: CrafterActions,
+    // generations in a row where the best fitness didn't improve
+    stagnation_counter: i32,
+    elapsed_ms: i64,
+    // every finished rotation the islands came up with, only when `solver.paretoFront` is set
+    archive: Option<ParetoArchive>,
+    // kept once the run is over so later steps don't run the ga again
+    result: Option<SimStep>,
+}
+
+impl GeneticBackend {
+    pub(crate) fn new(synth: Synth) -> Self {
+        let number_of_available_actions = synth.crafter.actions.len() as u8;
+        let population = synth.solver_vars.population.max(1) as usize;
+        #[cfg(feature = "wasm-thread")]
+        let population = {
+            log(&format!("USING {} cores", rayon::current_num_threads()));
+            (population / rayon::current_num_threads()).max(1)
+        };
+        let island_count = (synth.solver_vars.sub_populations as usize).clamp(1, population);
+        let population_size = population / island_count;
+        #[cfg(target_arch = "wasm32")]
+        log(&format!(
+            "population_size: {} on {} islands",
+            population_size, island_count
+        ));
+        // hands out the seeds for every island when the synth has a seed, otherwise genevo picks its own
+        let mut seeds = synth.seed.map(ChaCha8Rng::seed_from_u64);
+        // every island starts with a tenth of its population built from the user's sequence and the heuristic one
+        let seed_genomes = vec![
+            genome_from_actions(&synth, &synth.sequence),
+            genome_from_actions(&synth, &heuristic_sequence_builder(&synth)),
+        ];
+        let islands = (0..island_count)
+            .map(|index| {
+                let builder = build_population()
+                    .with_genome_builder(
+                        CraftActionGenomeBuilder::new(
+                            &synth,
+                            1,
+                            number_of_available_actions + 1, // 1 is our real first ability
+                        )
+                        .with_seed_genomes(seed_genomes.clone(), population_size / 10),
+                    )
+                    .of_size(population_size);
+                let initial_population: Population<CrafterActions> = match next_seed(&mut seeds) {
+                    Some(seed) => builder.using_seed(seed),
+                    None => builder.uniform_at_random(),
+                };
+                Island::new(
+                    &synth,
+                    index,
+                    island_count,
+                    initial_population,
+                    next_seed(&mut seeds),
+                )
+            })
+            .collect();
+
+        let archive = synth.solver_vars.pareto_front.then(ParetoArchive::default);
         Self {
             generations: 0,
             synth,
-            sim,
+            islands,
+            best_fitness: None,
+            best_genome: CrafterActions::new(),
+            stagnation_counter: 0,
+            elapsed_ms: 0,
+            archive,
+            result: None,
         }
     }
 
-    pub fn next_generation(&mut self) -> SimStep {
+    /// Keeps track of the best individual across every island.
+    /// Returns true once `maxStagnationCounter` generations have passed without the best fitness improving,
+    /// a counter of 0 never stops the run early.
+    fn record_generation(&mut self, best: Option<(i32, &CrafterActions)>) -> bool {
+        match (self.best_fitness, best) {
+            (Some(best_fitness), Some((fitness, _))) if fitness <= best_fitness => {
+                self.stagnation_counter += 1
+            }
+            (_, Some((fitness, genome))) => {
+                self.best_fitness = Some(fitness);
+                self.best_genome = genome.clone();
+                self.stagnation_counter = 0;
+            }
+            (_, None) => self.stagnation_counter += 1,
+        }
+        let max_stagnation = self.synth.solver_vars.max_stagnation_counter;
+        max_stagnation > 0 && self.stagnation_counter >= max_stagnation
+    }
+
+    /// Ring migration, the best individuals of every island join the next generation of the island after it
+    fn migrate(&mut self, migrants: Vec<Vec<CrafterActions>>) {
+        let count = self.islands.len();
+        for (index, migrants) in migrants.into_iter().enumerate() {
+            let island = &self.islands[(index + 1) % count];
+            island.migrants.lock().unwrap().extend(migrants);
+        }
+    }
+
+    /// Polishes the best genome with a local search and reports it,
+    /// or the whole Pareto front when the run keeps one
+    fn finish(&mut self, stop_condition: StopCondition) -> SimStep {
+        let elapsed_time = self.elapsed_ms / 1000;
+        let polished = polish(&self.synth, &self.best_genome);
+        let genome = match self.best_fitness {
+            Some(best_fitness) if self.synth.fitness_of(&polished) < best_fitness => {
+                self.best_genome.clone()
+            }
+            _ => polished,
+        };
+        let mut log = Some("Final State Log\n".to_string());
+        let (state, steps) = genome.get_final_actions_list(&self.synth, &mut log);
+        let mut log = log.unwrap();
+        let _ = write!(
+            log,
+            "\nFinal State: \n{:#?}\nDuration {}\n Stop Reason: {}\nLocal search fitness: {} -> {}",
+            state,
+            elapsed_time,
+            stop_condition,
+            self.best_fitness.unwrap_or_default(),
+            self.synth.fitness_of(&genome)
+        );
+        if let Some(mut archive) = self.archive.take() {
+            archive.insert(&self.synth, &genome);
+            return SimStep::ParetoFront {
+                front: archive.into_front(),
+                execution_log: log,
+                elapsed_time: Some(elapsed_time),
+                stop_condition,
+            };
+        }
+        SimStep::Success {
+            best_sequence: steps,
+            execution_log: log,
+            elapsed_time: Some(elapsed_time),
+            hq_percent: hq_percent(state.quality_state, self.synth.recipe.max_quality) as f32,
+            stop_condition,
+        }
+    }
+}
+
+impl SolverBackend for GeneticBackend {
+    fn next_generation(&mut self) -> SimStep {
+        if let Some(result) = &self.result {
+            return result.clone();
+        }
         self.generations += 1;
-        match self.sim.step() {
-            Ok(ok) => match ok {
-                SimResult::Intermediate(a) => {
-                    let genome = &a.result.best_solution.solution.genome;
-                    let mut work_log = Some(String::new());
-                    let (state, best_sequence) =
-                        genome.get_final_actions_list(&self.synth, &mut work_log);
-                    // #[cfg(target_arch = "wasm32")]
-                    // log(&format!(
-                    //     "gen: {} {}, best fitness {} actions {:?}\n worklog:\n{}",
-                    //     self.generations,
-                    //     a.processing_time,
-                    //     a.result.best_solution.solution.fitness,
-                    //     best_sequence,
-                    //     work_log.unwrap()
-                    // ));
-                    SimStep::Progress {
-                        generations_completed: self.generations,
-                        max_generations: self.synth.solver_vars.generations as u32,
-                        best_sequence,
-                        state: state.into(),
-                    }
-                }
-                SimResult::Final(a, b, c, d) => {
-                    let genome = &a.result.best_solution.solution.genome;
-                    let mut log = Some("Final State Log\n".to_string());
-                    let (state, steps) = genome.get_final_actions_list(&self.synth, &mut log);
-                    let mut log = log.unwrap();
-                    let _ = write!(
-                        log,
-                        "\nFinal State: \n{:#?}\nDuration {}\n Stop Reason: {}",
-                        state, c, d
-                    );
-                    SimStep::Success {
-                        best_sequence: steps,
-                        execution_log: log,
-                        elapsed_time: Some(b.duration().num_seconds()),
-                    }
+        let interval = self.synth.solver_vars.migration_interval;
+        let migrating =
+            self.islands.len() > 1 && interval > 0 && self.generations.is_multiple_of(interval);
+        let mut generation_best: Option<(i32, CrafterActions)> = None;
+        let mut migrants = Vec::with_capacity(self.islands.len());
+        for island in &mut self.islands {
+            // the generation limit is checked below, so the last generation is treated like any other
+            let state = match island.sim.step() {
+                Ok(SimResult::Intermediate(state)) | Ok(SimResult::Final(state, ..)) => state,
+                Err(e) => return SimStep::Error(e.to_string()),
+            };
+            self.elapsed_ms += state.duration.num_milliseconds();
+            let best = &state.result.best_solution.solution;
+            if generation_best
+                .as_ref()
+                .is_none_or(|(fitness, _)| best.fitness > *fitness)
+            {
+                generation_best = Some((best.fitness, best.genome.clone()));
+            }
+            if let Some(archive) = &mut self.archive {
+                for genome in state.result.evaluated_population.individuals().iter() {
+                    archive.insert(&self.synth, genome);
                 }
-            },
-            Err(e) => SimStep::Error(e.to_string()),
+            }
+            if migrating {
+                let evaluated = &state.result.evaluated_population;
+                let mut best: Vec<(i32, &CrafterActions)> = evaluated
+                    .fitness_values()
+                    .iter()
+                    .copied()
+                    .zip(evaluated.individuals())
+                    .collect();
+                best.sort_by_key(|(fitness, _)| Reverse(*fitness));
+                migrants.push(
+                    best.into_iter()
+                        .take(self.synth.solver_vars.migrants)
+                        .map(|(_, genome)| genome.clone())
+                        .collect(),
+                );
+            }
+        }
+        let stagnant = self.record_generation(generation_best.as_ref().map(|(f, g)| (*f, g)));
+        let stop_condition = if stagnant {
+            Some(StopCondition::Stagnation)
+        } else if self.generations >= self.synth.solver_vars.generations as u32 {
+            Some(StopCondition::GenerationLimit)
+        } else {
+            None
+        };
+        if let Some(stop_condition) = stop_condition {
+            let result = self.finish(stop_condition);
+            self.result = Some(result.clone());
+            return result;
+        }
+        if migrating {
+            self.migrate(migrants);
+        }
+
+        let mut work_log = Some(String::new());
+        let (state, best_sequence) = self
+            .best_genome
+            .get_final_actions_list(&self.synth, &mut work_log);
+        SimStep::Progress {
+            generations_completed: self.generations,
+            max_generations: self.synth.solver_vars.generations as u32,
+            best_sequence,
+            state: state.into(),
         }
     }
 }
 
+#[wasm_bindgen]
+pub struct CraftSimulator {
+    pub(crate) backend: Box<dyn SolverBackend>,
+}
+
+impl CraftSimulator {
+    /// Builds the backend picked by `algorithm` or `solver.algorithm`, defaulting to the genetic algorithm
+    pub fn new(synth: Synth) -> Self {
+        let backend: Box<dyn SolverBackend> = match synth.algorithm() {
+            Ok(Algorithm::EaComplex) => Box::new(GeneticBackend::new(synth)),
+            Ok(Algorithm::Exhaustive) => Box::new(ExhaustiveBackend::new(synth)),
+            Err(error) => Box::new(ErrorBackend(error)),
+        };
+        Self { backend }
+    }
+
+    pub fn next_generation(&mut self) -> SimStep {
+        self.backend.next_generation()
+    }
+}
+
 /// State that gets posted to the JS
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -281,6 +600,8 @@ pub struct StatusState {
     violations: Violations,
     condition: Condition,
     bonus_max_cp: i32,
+    /// Index of the highest of the recipe's quality tiers reached
+    tier: Option<usize>,
 }
 
 impl From<State<'_>> for StatusState {
@@ -291,11 +612,37 @@ impl From<State<'_>> for StatusState {
             durability: state.durability_state,
             cp: state.cp_state,
             progress: state.progress_state,
-            hq_percent: 0.0, // TODO hq percent calculation
+            hq_percent: hq_percent(state.quality_state, state.synth.recipe.max_quality) as f32,
             feasible: violations.is_okay() && violations.progress_ok,
             violations,
             condition: state.condition,
             bonus_max_cp: state.bonus_max_cp,
+            tier: state.synth.recipe.tier_reached(state.quality_state),
+        }
+    }
+}
+
+/// Why a solver stopped
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum StopCondition {
+    /// Ran every generation in `solver.generations`
+    GenerationLimit,
+    /// The best fitness didn't improve for `solver.maxStagnationCounter` generations
+    Stagnation,
+    /// An exhaustive search finished
+    SearchComplete,
+    /// Stopped early from the JS
+    Paused,
+}
+
+impl Display for StopCondition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StopCondition::GenerationLimit => write!(f, "Reached the generation limit"),
+            StopCondition::Stagnation => write!(f, "Best fitness stopped improving"),
+            StopCondition::SearchComplete => write!(f, "Search complete"),
+            StopCondition::Paused => write!(f, "Paused"),
         }
     }
 }
@@ -308,6 +655,17 @@ pub enum SimStep {
         best_sequence: Vec<Action>,
         execution_log: String,
         elapsed_time: Option<i64>,
+        hq_percent: f32,
+        stop_condition: StopCondition,
+    },
+    /// Every rotation that isn't beaten on quality, step count, leftover CP and leftover durability at once,
+    /// highest quality first
+    #[serde(rename_all = "camelCase")]
+    ParetoFront {
+        front: Vec<ParetoPoint>,
+        execution_log: String,
+        elapsed_time: Option<i64>,
+        stop_condition: StopCondition,
     },
     #[serde(rename_all = "camelCase")]
     Progress {
@@ -332,9 +690,16 @@ impl CraftSimulator {
     pub fn new_wasm(synth: JsValue) -> Self {
         console_error_panic_hook::set_once();
         log(&format!("RUST SEES OBJECT {:?}", synth));
-        let synth = serde_wasm_bindgen::from_value(synth).unwrap();
-        log(&format!("Loaded synth {:?}", &synth));
-        Self::new(synth)
+        // bad input, like an algorithm that doesn't exist, is reported to the JS instead of panicking
+        match serde_wasm_bindgen::from_value::<Synth>(synth) {
+            Ok(synth) => {
+                log(&format!("Loaded synth {:?}", &synth));
+                Self::new(synth)
+            }
+            Err(error) => Self {
+                backend: Box::new(ErrorBackend(error.to_string())),
+            },
+        }
     }
 
     pub fn next_wasm(&mut self) -> JsValue {
@@ -343,11 +708,18 @@ impl CraftSimulator {
 
     pub fn pause_wasm(&mut self) -> JsValue {
         let mut value = self.next_generation();
-        if let SimStep::Progress { best_sequence, .. } = value {
+        if let SimStep::Progress {
+            best_sequence,
+            state,
+            ..
+        } = value
+        {
             value = SimStep::Success {
                 best_sequence,
                 execution_log: "".to_string(),
                 elapsed_time: None,
+                hq_percent: state.hq_percent,
+                stop_condition: StopCondition::Paused,
             };
         }
 
@@ -358,9 +730,18 @@ impl CraftSimulator {
 #[cfg(test)]
 mod tests {
     use crate::actions::Action;
-    use crate::simulator::{CalcState, CraftSimulator, CrafterActions, SimStep};
-    use crate::xiv_model::{Crafter, Recipe, SolverVars, Synth};
+    use crate::crossover::Crossover;
+    use crate::genome::genome_from_actions;
+    use crate::simulator::{
+        next_seed, CalcState, CraftSimulator, CrafterActions, GeneticBackend, SimStep,
+        SolverBackend, StatusState, StopCondition,
+    };
+    use crate::xiv_model::{
+        Algorithm, Crafter, CraftingClass, QualityTier, Recipe, SolverVars, Synth,
+    };
     use genevo::genetic::FitnessFunction;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
     use smallvec::SmallVec;
 
     const TEST_STR: &str = r#"{"crafter":{"level":78,"craftsmanship":863,"control":877,"cp":412,"actions":["muscleMemory","reflect","basicSynth2","carefulSynthesis","groundwork","intensiveSynthesis","delicateSynthesis","basicTouch","standardTouch","byregotsBlessing","preciseTouch","prudentTouch","preparatoryTouch","tricksOfTheTrade","mastersMend","wasteNot","wasteNot2","veneration","greatStrides","innovation","finalAppraisal","observe"]},"recipe":{"cls":"Weaver","level":390,"difficulty":1195,"durability":60,"startQuality":0,"safetyMargin":0,"maxQuality":3010,"baseLevel":71,"progressDivider":101,"progressModifier":100,"qualityDivider":81,"qualityModifier":100,"suggestedControl":1220,"suggestedCraftsmanship":1320,"name":"Custom Gathering Tool Components"},"sequence":[],"algorithm":"eaComplex","maxTricksUses":0,"maxMontecarloRuns":400,"reliabilityPercent":100,"useConditions":false,"maxLength":50,"solver":{"algorithm":"eaComplex","penaltyWeight":10000,"population":12000,"subPopulations":10,"solveForCompletion":false,"remainderCPFitnessValue":10,"remainderDurFitnessValue":100,"maxStagnationCounter":25,"generations":2000},"debug":true}"#;
@@ -393,6 +774,41 @@ mod tests {
         assert!(fitness < 0);
     }
 
+    #[test]
+    fn fitness_config() {
+        let synth: Synth = serde_json::from_str(TEST_STR).unwrap();
+        assert_eq!(synth.solver_vars.fitness.penalty_weight, 10000.0);
+        assert_eq!(synth.solver_vars.fitness.step_bonus, 4.0);
+        // muscle memory, veneration, groundwork, groundwork, careful synthesis finishes the craft
+        let rotation: CrafterActions = SmallVec::from_slice(&[0, 17, 4, 4, 3]);
+        let base = synth.fitness_of(&rotation);
+        let state = rotation.calculate_final_state(&synth, &mut None);
+
+        let tuned: Synth = serde_json::from_str(&TEST_STR.replace(
+            r#""penaltyWeight":10000"#,
+            r#""penaltyWeight":10000,"leftoverCpWeight":1,"actionCosts":{"groundwork":50}"#,
+        ))
+        .unwrap();
+        assert_eq!(tuned.solver_vars.fitness.step_bonus, 4.0);
+        assert_eq!(tuned.fitness_of(&rotation), base + state.cp_state - 2 * 50);
+
+        // careful observation doesn't take a step, the finishing groundwork still has to be paid for
+        let mut observing = synth.clone();
+        observing.crafter.specialist = true;
+        observing.crafter.actions.push(Action::CarefulObservation);
+        let rotation: CrafterActions = SmallVec::from_slice(&[22, 0, 17, 4, 4]);
+        let base = observing.fitness_of(&rotation);
+        observing.solver_vars.fitness.action_costs =
+            [(Action::Groundwork, 50.0)].into_iter().collect();
+        assert_eq!(observing.fitness_of(&rotation), base - 2 * 50);
+
+        // unfinished crafts are scaled by the penalty weight
+        let unfinished: CrafterActions = SmallVec::from_slice(&[0, 17]);
+        let mut light = synth.clone();
+        light.solver_vars.fitness.penalty_weight = 1.0;
+        assert!(light.fitness_of(&unfinished) > synth.fitness_of(&unfinished));
+    }
+
     #[test]
     fn test_real_actions() {
         let mut synth: Synth = serde_json::from_str(TEST_STR).unwrap();
@@ -401,17 +817,349 @@ mod tests {
         let _ = sim.next_generation();
     }
 
+    #[test]
+    fn picks_algorithm() {
+        // the top level algorithm is enough on its own
+        let top_level: Synth = serde_json::from_str(
+            &SMOL_ABILITY
+                .replace(
+                    r#""algorithm":"eaComplex","penaltyWeight""#,
+                    r#""penaltyWeight""#,
+                )
+                .replace(r#""algorithm":"eaComplex""#, r#""algorithm":"exhaustive""#),
+        )
+        .unwrap();
+        assert_eq!(top_level.algorithm(), Ok(Algorithm::Exhaustive));
+
+        // the two fields disagreeing is an error step instead of a guess
+        let conflicting: Synth = serde_json::from_str(&SMOL_ABILITY.replace(
+            r#""algorithm":"eaComplex","penaltyWeight""#,
+            r#""algorithm":"exhaustive","penaltyWeight""#,
+        ))
+        .unwrap();
+        assert!(conflicting.algorithm().is_err());
+        let mut sim = CraftSimulator::new(conflicting);
+        assert!(matches!(sim.next_generation(), SimStep::Error(_)));
+
+        let unknown = SMOL_ABILITY.replace(r#""eaComplex""#, r#""annealing""#);
+        assert!(serde_json::from_str::<Synth>(&unknown).is_err());
+    }
+
+    #[test]
+    fn exhaustive_backend() {
+        let mut synth: Synth = serde_json::from_str(
+            &SMOL_ABILITY.replace(r#""algorithm":"eaComplex""#, r#""algorithm":"exhaustive""#),
+        )
+        .unwrap();
+        assert_eq!(synth.algorithm(), Ok(Algorithm::Exhaustive));
+        synth.max_length = 8;
+        let mut sim = CraftSimulator::new(synth);
+        let first = loop {
+            match sim.next_generation() {
+                SimStep::Progress { .. } => continue,
+                step => break step,
+            }
+        };
+        match &first {
+            SimStep::Success {
+                best_sequence,
+                elapsed_time,
+                hq_percent,
+                stop_condition,
+                ..
+            } => {
+                assert_ne!(best_sequence.len(), 0);
+                assert!(elapsed_time.is_some());
+                assert!(*hq_percent >= 1.0);
+                assert_eq!(*stop_condition, StopCondition::SearchComplete);
+            }
+            _ => panic!("exhaustive search should finish"),
+        }
+        // asking again gives back the same answer instead of searching again
+        match (first, sim.next_generation()) {
+            (
+                SimStep::Success { best_sequence, .. },
+                SimStep::Success {
+                    best_sequence: again,
+                    ..
+                },
+            ) => assert_eq!(best_sequence, again),
+            _ => panic!("exhaustive search should stay finished"),
+        }
+    }
+
+    /// Steps the simulator until it finishes, returning how it stopped and after how many steps
+    fn run_to_end(sim: &mut CraftSimulator) -> (StopCondition, u32) {
+        let mut steps = 0;
+        loop {
+            steps += 1;
+            match sim.next_generation() {
+                SimStep::Success { stop_condition, .. }
+                | SimStep::ParetoFront { stop_condition, .. } => return (stop_condition, steps),
+                SimStep::Progress { .. } => {}
+                SimStep::Error(e) => panic!("{}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn stagnation_stops_early() {
+        let mut synth: Synth = serde_json::from_str(SMOL_ABILITY).unwrap();
+        synth.solver_vars.population = 200;
+        synth.solver_vars.max_stagnation_counter = 5;
+        let mut sim = CraftSimulator::new(synth);
+        let (stop_condition, steps) = run_to_end(&mut sim);
+        assert_eq!(stop_condition, StopCondition::Stagnation);
+        assert!(steps < 1000);
+        // the run stays finished
+        assert!(matches!(sim.next_generation(), SimStep::Success { .. }));
+    }
+
+    #[test]
+    fn generation_limit() {
+        let mut synth: Synth = serde_json::from_str(SMOL_ABILITY).unwrap();
+        synth.solver_vars.population = 200;
+        synth.solver_vars.generations = 10;
+        synth.solver_vars.max_stagnation_counter = 0;
+        let mut sim = CraftSimulator::new(synth);
+        assert_eq!(run_to_end(&mut sim), (StopCondition::GenerationLimit, 10));
+    }
+
+    #[test]
+    fn island_model() {
+        let mut synth: Synth = serde_json::from_str(SMOL_ABILITY).unwrap();
+        synth.solver_vars.population = 400;
+        synth.solver_vars.generations = 25;
+        synth.solver_vars.max_stagnation_counter = 0;
+        assert_eq!(synth.solver_vars.sub_populations, 10);
+        // runs past a couple of migrations
+        let mut sim = CraftSimulator::new(synth);
+        assert_eq!(run_to_end(&mut sim), (StopCondition::GenerationLimit, 25));
+        match sim.next_generation() {
+            SimStep::Success { best_sequence, .. } => assert_ne!(best_sequence.len(), 0),
+            _ => panic!("the run should be finished"),
+        }
+    }
+
+    #[test]
+    fn migrants_join_next_generation() {
+        let mut synth: Synth = serde_json::from_str(SMOL_ABILITY).unwrap();
+        synth.solver_vars.population = 300;
+        synth.solver_vars.sub_populations = 3;
+        synth.solver_vars.max_stagnation_counter = 0;
+        assert_eq!(synth.solver_vars.migration_interval, 10);
+        synth.solver_vars.migration_interval = 5;
+        synth.solver_vars.migrants = 3;
+        let mut backend = GeneticBackend::new(synth);
+        for _ in 0..5 {
+            backend.next_generation();
+        }
+        for island in &backend.islands {
+            assert_eq!(island.migrants.lock().unwrap().len(), 3);
+        }
+        // the islands keep running and pick the migrants up as offspring
+        backend.next_generation();
+        for island in &backend.islands {
+            assert!(island.migrants.lock().unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn quality_tiers() {
+        let mut synth: Synth = serde_json::from_str(TEST_STR).unwrap();
+        // muscle memory, veneration, groundwork, basic touch, groundwork
+        let rotation: CrafterActions = SmallVec::from_slice(&[0, 17, 4, 7, 4]);
+        let quality = rotation
+            .calculate_final_state(&synth, &mut None)
+            .quality_state;
+        assert!(quality > 0);
+        synth.recipe.quality_tiers = vec![
+            QualityTier::Collectability(quality as u32 / 10 + 1),
+            QualityTier::Quality(quality as u32),
+        ];
+        let max_quality_fitness = synth.fitness_of(&rotation);
+        let state: StatusState = rotation.calculate_final_state(&synth, &mut None).into();
+        assert_eq!(state.tier, Some(1));
+
+        // reaching the tier is worth as much as reaching max quality, so the step bonus kicks in
+        synth.solver_vars.target_tier = Some(1);
+        assert_eq!(synth.target_quality(), quality as u32);
+        assert!(synth.fitness_of(&rotation) > max_quality_fitness);
+        synth.solver_vars.target_tier = Some(0);
+        assert_eq!(synth.fitness_of(&rotation), max_quality_fitness);
+    }
+
+    #[test]
+    fn robust_fitness() {
+        let mut synth: Synth = serde_json::from_str(TEST_STR).unwrap();
+        synth.crafter.actions.push(Action::HastyTouch);
+        let mut robust = synth.clone();
+        robust.solver_vars.fitness.robust = Some(Default::default());
+        // muscle memory, veneration, groundwork, basic touch, groundwork can't fail
+        let safe = genome_from_actions(
+            &synth,
+            &[
+                Action::MuscleMemory,
+                Action::Veneration,
+                Action::Groundwork,
+                Action::BasicTouch,
+                Action::Groundwork,
+            ],
+        );
+        assert_eq!(synth.fitness_of(&safe), robust.fitness_of(&safe));
+        // hasty touch is scored on its expected quality, the 10th percentile run misses it
+        let risky = genome_from_actions(
+            &synth,
+            &[
+                Action::MuscleMemory,
+                Action::Veneration,
+                Action::Groundwork,
+                Action::HastyTouch,
+                Action::Groundwork,
+            ],
+        );
+        assert!(robust.fitness_of(&risky) < synth.fitness_of(&risky));
+        assert!(robust.fitness_of(&risky) < robust.fitness_of(&safe));
+    }
+
+    #[test]
+    fn pareto_front() {
+        let mut synth: Synth = serde_json::from_str(TEST_STR).unwrap();
+        synth.seed = Some(7);
+        synth.solver_vars.population = 400;
+        synth.solver_vars.sub_populations = 2;
+        synth.solver_vars.generations = 15;
+        synth.solver_vars.pareto_front = true;
+        let mut sim = CraftSimulator::new(synth);
+        assert_eq!(run_to_end(&mut sim), (StopCondition::GenerationLimit, 15));
+        let front = match sim.next_generation() {
+            SimStep::ParetoFront { front, .. } => front,
+            step => panic!("expected a pareto front, got {:?}", step),
+        };
+        assert!(!front.is_empty());
+        // sorted by quality, and a lower quality rotation only stays if it wins somewhere else
+        for pair in front.windows(2) {
+            let (better, other) = (&pair[0], &pair[1]);
+            assert!(better.quality >= other.quality);
+            assert!(
+                other.steps < better.steps
+                    || other.cp > better.cp
+                    || other.durability > better.durability
+            );
+        }
+    }
+
+    #[test]
+    fn seeded_runs_are_repeatable() {
+        let mut synth: Synth = serde_json::from_str(TEST_STR).unwrap();
+        synth.seed = Some(1337);
+        synth.solver_vars.population = 400;
+        synth.solver_vars.sub_populations = 4;
+        synth.solver_vars.generations = 25;
+        let run = |synth: Synth| {
+            let mut sim = CraftSimulator::new(synth);
+            (0..25)
+                .map(|_| match sim.next_generation() {
+                    SimStep::Progress { best_sequence, .. } => best_sequence,
+                    SimStep::Success { best_sequence, .. } => best_sequence,
+                    step => panic!("{:?}", step),
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(run(synth.clone()), run(synth));
+    }
+
+    #[test]
+    fn seeds_are_portable() {
+        // the island seeds have to come out the same on wasm32 and native, or a seed shared between them finds a different rotation
+        let mut seeds = Some(ChaCha8Rng::seed_from_u64(1337));
+        let first = next_seed(&mut seeds).unwrap();
+        assert_eq!(
+            first,
+            [
+                174, 73, 24, 134, 52, 26, 83, 154, 28, 207, 175, 252, 201, 199, 134, 80, 173, 26,
+                220, 98, 112, 98, 12, 136, 43, 141, 41, 191, 107, 155, 196, 205
+            ]
+        );
+        assert_ne!(next_seed(&mut seeds).unwrap(), first);
+    }
+
+    #[test]
+    fn starts_from_sequence() {
+        let mut synth: Synth = serde_json::from_str(TEST_STR).unwrap();
+        // hasty touch isn't one of the crafter's actions so it gets dropped
+        synth.sequence = vec![
+            Action::MuscleMemory,
+            Action::Veneration,
+            Action::HastyTouch,
+            Action::Groundwork,
+            Action::Groundwork,
+            Action::CarefulSynthesis,
+        ];
+        let genome = genome_from_actions(&synth, &synth.sequence);
+        assert_eq!(genome.as_slice(), &[0, 17, 4, 4, 3]);
+        synth.seed = Some(1);
+        synth.solver_vars.population = 100;
+        synth.solver_vars.sub_populations = 1;
+        let mut sim = CraftSimulator::new(synth);
+        match sim.next_generation() {
+            SimStep::Progress { state, .. } => assert!(state.progress >= 1195),
+            _ => panic!("expected the solver to still be running"),
+        }
+    }
+
+    #[test]
+    fn heuristic_seeds_population() {
+        let mut synth: Synth = serde_json::from_str(TEST_STR).unwrap();
+        synth.seed = Some(1);
+        synth.solver_vars.population = 100;
+        synth.solver_vars.sub_populations = 1;
+        let mut sim = CraftSimulator::new(synth);
+        // the heuristic sequence already finishes the craft, random genomes rarely do
+        match sim.next_generation() {
+            SimStep::Progress { state, .. } => {
+                assert!(state.violations.progress_ok);
+                assert!(state.violations.durability_ok);
+                assert!(state.violations.cp_ok);
+            }
+            _ => panic!("expected the solver to still be running"),
+        }
+    }
+
+    #[test]
+    fn every_crossover_runs() {
+        for crossover in [
+            Crossover::SinglePoint,
+            Crossover::TwoPoint,
+            Crossover::Uniform,
+            Crossover::Phase,
+        ] {
+            let mut synth: Synth = serde_json::from_str(TEST_STR).unwrap();
+            synth.solver_vars.crossover = crossover;
+            synth.solver_vars.population = 200;
+            synth.solver_vars.generations = 5;
+            let mut sim = CraftSimulator::new(synth);
+            let (_, steps) = run_to_end(&mut sim);
+            assert_eq!(steps, 5);
+        }
+    }
+
     #[test]
     fn lvl50_cul_synth() {
         let synth : Synth = serde_json::from_str(r#"{"crafter":{"level":51,"craftsmanship":117,"control":158,"cp":180,"actions":["basicSynth2","basicTouch","standardTouch","byregotsBlessing","tricksOfTheTrade","mastersMend","wasteNot","wasteNot2","veneration","greatStrides","innovation","observe"]},"recipe":{"cls":"Culinarian","level":40,"difficulty":138,"durability":60,"startQuality":0,"maxQuality":3500,"baseLevel":40,"progressDivider":50,"progressModifier":100,"qualityDivider":30,"qualityModifier":100,"suggestedControl":68,"suggestedCraftsmanship":136,"name":"Grade 4 Skybuilders' Sesame Cookie","safetyMargin":0},"sequence":[],"algorithm":"eaComplex","maxTricksUses":0,"maxMontecarloRuns":400,"reliabilityPercent":100,"useConditions":false,"maxLength":0,"solver":{"algorithm":"eaComplex","penaltyWeight":10000,"population":10000,"subPopulations":10,"solveForCompletion":false,"remainderCPFitnessValue":10,"remainderDurFitnessValue":100,"maxStagnationCounter":25,"generations":1000},"debug":true}"#).unwrap();
         let mut sim = CraftSimulator::new(synth);
         let next = sim.next_generation();
         match next {
-            SimStep::Success { .. } => {
+            SimStep::Success { .. } | SimStep::ParetoFront { .. } => {
                 assert!(false);
             }
-            SimStep::Progress { best_sequence, .. } => {
+            SimStep::Progress {
+                best_sequence,
+                state,
+                ..
+            } => {
                 assert_ne!(best_sequence, vec![]);
+                assert!(state.hq_percent >= 1.0);
             }
             SimStep::Error(_) => {
                 assert!(false);
@@ -422,6 +1170,7 @@ mod tests {
     #[test]
     fn test_basic_synth() {
         let recipe = Recipe {
+            cls: Some(CraftingClass::Culinarian),
             base_level: 1,
             level: 1,
             difficulty: 100,
@@ -436,9 +1185,10 @@ mod tests {
             quality_divider: 1.0,
             quality_modifier: None,
             stars: None,
+            quality_tiers: vec![],
         };
         let crafter = Crafter {
-            //cls: 10,
+            cls: Some(CraftingClass::Culinarian),
             craftsmanship: 20,
             control: 20,
             craft_points: 10,
@@ -458,12 +1208,13 @@ mod tests {
                 generations: 750,
                 ..Default::default()
             },
+            ..Default::default()
         };
 
         let mut sim = CraftSimulator::new(synth);
         let sim_result = sim.next_generation();
         match sim_result {
-            SimStep::Success { .. } => {
+            SimStep::Success { .. } | SimStep::ParetoFront { .. } => {
                 assert!(false)
             }
             SimStep::Progress { best_sequence, .. } => {
@@ -1,190 +1,804 @@
-use std::cmp::Ordering;
-use crate::actions::{Action, ActionType};
-use crate::xiv_model::{State, Synth};
-#[cfg(feature = "thread")]
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use crate::actions::Action;
+use crate::hq::hq_percent;
+use crate::simulator::{SimStep, SolverBackend, StopCondition};
+use crate::xiv_model::{Condition, Effects, SimulationCondition, State, Synth};
+use chrono::Utc;
+use std::collections::HashMap;
+use std::fmt::Write;
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::JsValue;
 
 /// Attempts to use brute force to find *the* definitively best solution
 /// Will find the shortest synth possible that achieves it's goals the highest quality
+/// Only actions that always succeed are considered, so the result doesn't rely on conditions or luck.
 /// # Arguments
-/// * synth - Synth you want to solve for
-/// * goals - Goals of the synth
-
+/// * synth - Synth you want to solve for, `max_length` limits the number of steps when it isn't 0
 pub fn find_solution(synth: &Synth) -> Vec<Action> {
-    let state = State::from(synth);
-    better_options(state, &synth)
-        .map(|(mut a, s)| {
-            a.reverse();
-            println!("{a:?} {s:?}");
-            a
-        })
-        .unwrap_or_default() // default none = no actions were found
+    let mut search = Search::new(synth);
+    search.run(synth, usize::MAX);
+    search.best.map(|best| best.actions).unwrap_or_default() // default none = no actions were found
 }
 
+#[wasm_bindgen]
+pub fn find_solution_wasm(synth: JsValue) -> JsValue {
+    console_error_panic_hook::set_once();
+    let synth: Synth = serde_wasm_bindgen::from_value(synth).unwrap();
+    serde_wasm_bindgen::to_value(&find_solution(&synth)).unwrap()
+}
 
-fn add_action<'a>(action: Action, state: &State<'a>, synth: &'a Synth) -> Option<(Vec<Action>, State<'a>)> {
-    if action == Action::TricksOfTheTrade {
-        return None;
-    }
+/// Nodes the search visits per step, small enough that the wasm worker can report progress in between
+const NODES_PER_STEP: usize = 20_000;
 
-    let new_state = state.add_action(action);
-    // determine whether this is a complete state
-    if new_state.progress_state >= state.synth.recipe.difficulty as i32 {
-        let actions = vec![action];
-        return Some((actions, new_state));
-    }
-    // wasted actions for this are pointless.
-    if new_state.wasted_actions > 0.0 {
-        return None;
+/// Runs the same search as [find_solution] a slice at a time, later steps repeat the final result
+pub(crate) struct ExhaustiveBackend {
+    synth: Synth,
+    search: Search,
+    steps: u32,
+    elapsed_ms: i64,
+    result: Option<SimStep>,
+}
+
+impl ExhaustiveBackend {
+    pub(crate) fn new(synth: Synth) -> Self {
+        Self {
+            search: Search::new(&synth),
+            synth,
+            steps: 0,
+            elapsed_ms: 0,
+            result: None,
+        }
     }
-    if new_state.reliability < 0 {
-        return None;
+
+    /// Best rotation found so far
+    fn best_sequence(&self) -> Vec<Action> {
+        self.search
+            .best
+            .as_ref()
+            .map(|best| best.actions.clone())
+            .unwrap_or_default()
     }
-    if new_state.durability_state <= 0 {
-        return None;
+}
+
+impl SolverBackend for ExhaustiveBackend {
+    fn next_generation(&mut self) -> SimStep {
+        if let Some(result) = &self.result {
+            return result.clone();
+        }
+        let start = Utc::now();
+        let done = self.search.run(&self.synth, NODES_PER_STEP);
+        self.elapsed_ms += (Utc::now() - start).num_milliseconds();
+        self.steps += 1;
+        let synth = &self.synth;
+        let best_sequence = self.best_sequence();
+        if !done {
+            let mut condition = SimulationCondition::new_sim_condition(synth);
+            let state = best_sequence
+                .iter()
+                .fold(State::from(synth), |state, action| {
+                    state.add_action(*action, &mut condition)
+                });
+            // the size of the search isn't known up front, so there is always one more step to go
+            return SimStep::Progress {
+                generations_completed: self.steps,
+                max_generations: self.steps + 1,
+                best_sequence,
+                state: state.into(),
+            };
+        }
+        let mut condition = SimulationCondition::new_sim_condition(synth);
+        let mut state = State::from(synth);
+        let mut execution_log = format!("Final State Log\n{state}\n");
+        for action in &best_sequence {
+            state = state.add_action(*action, &mut condition);
+            let _ = writeln!(execution_log, "{state}");
+        }
+        let result = SimStep::Success {
+            best_sequence,
+            execution_log,
+            elapsed_time: Some(self.elapsed_ms / 1000),
+            hq_percent: hq_percent(state.quality_state, synth.recipe.max_quality) as f32,
+            stop_condition: StopCondition::SearchComplete,
+        };
+        self.result = Some(result.clone());
+        result
     }
-    // invalid states should return None
-    if new_state.cp_state < 0 {
-        return None;
+}
+
+/// Fields of the state that decide what can still happen in the craft
+#[derive(PartialEq, Eq, Hash)]
+struct StateKey {
+    progress: i32,
+    quality: i32,
+    durability: i32,
+    cp: i32,
+    action: Option<Action>,
+    effects: Effects,
+    condition: Condition,
+    touch_combo_step: i32,
+    heart_and_soul_used: bool,
+    careful_observation_uses: u8,
+    trained_perfection_used: bool,
+    quick_innovation_used: bool,
+    /// The bound and the children depend on the tricks still left
+    trick_uses: i32,
+    /// Expected value conditions change every step, so states are only equal at the same step
+    condition_step: Option<u32>,
+}
+
+impl From<&State<'_>> for StateKey {
+    fn from(state: &State<'_>) -> Self {
+        Self {
+            progress: state.progress_state,
+            quality: state.quality_state,
+            durability: state.durability_state,
+            cp: state.cp_state,
+            action: state.action,
+            effects: state.effects.clone(),
+            condition: state.condition,
+            touch_combo_step: state.touch_combo_step,
+            heart_and_soul_used: state.heart_and_soul_used,
+            careful_observation_uses: state.careful_observation_uses,
+            trained_perfection_used: state.trained_perfection_used,
+            quick_innovation_used: state.quick_innovation_used,
+            trick_uses: state.trick_uses,
+            condition_step: state.synth.use_conditions.then_some(state.last_step),
+        }
     }
-    // now try children of THIS action
-    let (mut actions, state) = better_options(new_state, synth)?;
-    actions.push(action);
-    Some((actions, state))
 }
 
+struct Best {
+    quality: i32,
+    actions: Vec<Action>,
+}
 
-fn filter_usable(action: Action, state: &State<'_>) -> bool {
-    let action = action;
-    match action {
-        Action::Observe => return false,
-        Action::MuscleMemory => {
-            if state.step > 1 {
-                return false;
-            }
+/// Relaxed model of the craft used to bound what is still reachable from a state.
+/// Every estimate here has to be optimistic, otherwise the search could prune the best rotation.
+struct Bounds {
+    base_quality: f32,
+    base_progress: f32,
+    /// Best multiplier of a regular touch
+    touch_multiplier: f32,
+    byregots_blessing: bool,
+    /// Most inner quiet stacks a single action can add
+    inner_quiet_gain: i32,
+    great_strides: bool,
+    /// Cp cost of innovation, `None` when the crafter doesn't have it
+    innovation_cp_cost: Option<i32>,
+    /// Quick innovation gives a single step of innovation without spending cp
+    quick_innovation: bool,
+    /// Best progress multiplier including veneration
+    progress_multiplier: f32,
+    /// An action that adds progress and quality together means no steps are reserved for progress
+    combined_action: bool,
+    /// Cheapest quality action, limits how many quality steps the remaining cp can pay for.
+    /// `None` when there are no quality actions at all
+    min_cp_cost: Option<i32>,
+    condition_multiplier: f32,
+    trained_eye: bool,
+    /// Cheapest action that costs any cp
+    min_step_cp: i32,
+    /// Least durability an action without a cp cost can use, `None` if every action costs cp
+    free_durability_cost: Option<i32>,
+    /// Cheapest repair and the most durability any repair gives back
+    repair: Option<(i32, i32)>,
+    tricks: bool,
+    careful_observation: bool,
+    trained_perfection: bool,
+    /// Quality gain bound cached by [BoundKey]
+    cache: HashMap<BoundKey, f32>,
+}
+
+#[derive(PartialEq, Eq, Hash)]
+struct BoundKey {
+    quality_steps: u32,
+    cp: i32,
+    stacks: i32,
+    great_strides: bool,
+    innovation_turns: i32,
+    quick_innovation: bool,
+}
+
+impl Bounds {
+    fn new(synth: &Synth, actions: &[Action]) -> Self {
+        let (base_progress, base_quality) = synth.calculate_progress_and_quality_increase();
+        let quality_actions = || {
+            actions
+                .iter()
+                .filter(|a| a.details().quality_increase_multiplier > 0.0)
+        };
+        let touch_multiplier = quality_actions()
+            .filter(|a| **a != Action::ByregotsBlessing)
+            .map(|a| a.details().quality_increase_multiplier)
+            .fold(0.0, f32::max);
+        let inner_quiet_gain = if actions.iter().any(|a| {
+            matches!(
+                a,
+                Action::PreparatoryTouch
+                    | Action::PreciseTouch
+                    | Action::RefinedTouch
+                    | Action::Reflect
+            )
+        }) {
+            2
+        } else {
+            1
+        };
+        let mut progress_multiplier = actions
+            .iter()
+            .map(|a| a.details().progress_increase_multiplier)
+            .fold(0.0, f32::max);
+        if actions.contains(&Action::Veneration) {
+            progress_multiplier *= 1.5;
         }
-        Action::Reflect => {
-            if state.step > 1 {
-                return false;
-            }
+        let repairs: Vec<(i32, i32)> = [
+            (Action::MastersMend, 30),
+            (Action::Manipulation, 40),
+            (Action::ImmaculateMend, synth.recipe.durability as i32),
+        ]
+        .into_iter()
+        .filter(|(action, _)| actions.contains(action))
+        .map(|(action, restored)| (action.details().cp_cost, restored))
+        .collect();
+        let repair = repairs
+            .iter()
+            .map(|(cp_cost, _)| *cp_cost)
+            .min()
+            .zip(repairs.iter().map(|(_, restored)| *restored).max());
+        Self {
+            base_quality: base_quality as f32,
+            base_progress: base_progress as f32,
+            touch_multiplier,
+            byregots_blessing: actions.contains(&Action::ByregotsBlessing),
+            inner_quiet_gain,
+            great_strides: actions.contains(&Action::GreatStrides),
+            innovation_cp_cost: actions
+                .contains(&Action::Innovation)
+                .then(|| Action::Innovation.details().cp_cost),
+            quick_innovation: actions.contains(&Action::QuickInnovation),
+            progress_multiplier,
+            combined_action: quality_actions()
+                .any(|a| a.details().progress_increase_multiplier > 0.0),
+            min_cp_cost: quality_actions().map(|a| a.details().cp_cost).min(),
+            condition_multiplier: if synth.use_conditions { 4.0 } else { 1.0 },
+            trained_eye: actions.contains(&Action::TrainedEye),
+            min_step_cp: actions
+                .iter()
+                .map(|a| a.details().cp_cost)
+                .filter(|cp_cost| *cp_cost > 0)
+                .min()
+                .unwrap_or(0),
+            // waste not can halve the durability cost
+            free_durability_cost: actions
+                .iter()
+                .map(|a| a.details())
+                .filter(|details| details.cp_cost == 0 && details.durability_cost > 0)
+                .map(|details| (details.durability_cost / 2).max(1))
+                .min(),
+            repair,
+            tricks: actions.contains(&Action::TricksOfTheTrade),
+            careful_observation: actions.contains(&Action::CarefulObservation),
+            trained_perfection: actions.contains(&Action::TrainedPerfection),
+            cache: HashMap::new(),
         }
-        Action::TrainedFinesse => return false,
-
-        _ => {}
-    }
-    // try to filter actions that don't make sense
-    let details = action.details();
-    // only rely on full success for solver
-    if details.success_probability < 1.0 {
-        return false;
     }
 
-    if details.on_excellent || details.on_good {
-        return false;
+    /// Tricks of the trade the crafter can still use
+    fn tricks_left(&self, state: &State<'_>) -> i32 {
+        if self.tricks {
+            (state.synth.max_trick_uses - state.trick_uses).max(0)
+        } else {
+            0
+        }
     }
 
-    if details.cp_cost < 5 && details.durability_cost < 10 {
-        if let Some(a) = state.action {
-            if action == a {
-                return false;
+    /// Most steps the craft can still take. Every step either spends cp, spends durability without spending cp,
+    /// or is one of the free actions that can only be used a few times.
+    fn max_steps(&self, state: &State<'_>) -> u32 {
+        let tricks = self.tricks_left(state);
+        let cp = state.cp_state.max(0) + tricks * 20;
+        let cp_steps = if self.min_step_cp > 0 {
+            cp / self.min_step_cp
+        } else {
+            0
+        };
+        let durability_steps = match self.free_durability_cost {
+            Some(durability_cost) => {
+                let restored = self
+                    .repair
+                    .map(|(cp_cost, restored)| cp / cp_cost * restored)
+                    .unwrap_or(0);
+                // the step that breaks the item still counts
+                (state.durability_state.max(0) + restored) / durability_cost + 1
             }
+            None => 0,
+        };
+        let mut free_steps = tricks;
+        if self.careful_observation {
+            free_steps += 3 - state.careful_observation_uses as i32;
+        }
+        if self.quick_innovation && !state.quick_innovation_used {
+            free_steps += 1;
+        }
+        // trained perfection is free and so is the durability of the step after it
+        if self.trained_perfection && !state.trained_perfection_used {
+            free_steps += 2;
         }
+        (cp_steps + durability_steps + free_steps).max(0) as u32
     }
 
-    let need_quality = state.synth.recipe.max_quality >= state.quality_state as u32;
-    // if our state has quality, we only need progress
-    if need_quality
-        && details.action_type == ActionType::Immediate
-        && details.progress_increase_multiplier == 0.0
-    {
-        // quality is full, and this action increases progress
-        return false;
+    /// Highest quality reachable from this state in at most `steps_left` more steps,
+    /// `None` if the craft can't be finished in time
+    fn max_quality(&mut self, state: &State<'_>, steps_left: u32) -> Option<i32> {
+        let max_quality = state.synth.recipe.max_quality as i32;
+        let remaining_progress = state.synth.recipe.difficulty as i32 - state.progress_state;
+        let mut progress_per_step = self.base_progress * self.progress_multiplier;
+        if state
+            .effects
+            .count_downs
+            .get(Action::MuscleMemory)
+            .is_some()
+        {
+            progress_per_step *= 2.0;
+        }
+        let progress_steps = (remaining_progress as f32 / progress_per_step)
+            .ceil()
+            .max(1.0) as u32;
+        if progress_per_step <= 0.0 || progress_steps > steps_left {
+            return None;
+        }
+        if self.trained_eye && state.step == 0 {
+            return Some(max_quality);
+        }
+        let quality_steps = if self.combined_action {
+            steps_left
+        } else {
+            steps_left - progress_steps
+        };
+        let key = BoundKey {
+            quality_steps,
+            // every trick left can pay for more touches
+            cp: state.cp_state + self.tricks_left(state) * 20,
+            stacks: state
+                .effects
+                .count_ups
+                .get(Action::InnerQuiet)
+                .map(|(_, i)| *i as i32 + 1)
+                .unwrap_or(0),
+            great_strides: state
+                .effects
+                .count_downs
+                .get(Action::GreatStrides)
+                .is_some(),
+            innovation_turns: state
+                .effects
+                .count_downs
+                .get(Action::Innovation)
+                .map(|(_, i)| *i as i32)
+                .unwrap_or(0),
+            quick_innovation: self.quick_innovation && !state.quick_innovation_used,
+        };
+        let gain = match self.cache.get(&key) {
+            Some(gain) => *gain,
+            None => {
+                let gain = self.quality_gain(&key);
+                self.cache.insert(key, gain);
+                gain
+            }
+        };
+        Some(((state.quality_state as f32 + gain).ceil() as i32).min(max_quality))
     }
-    // if we have a countdown active, and the action has more than two turns, skip this
-    if let ActionType::Countdown { active_turns } = details.action_type {
-        if let Some((_, turns)) = state.effects.count_downs.get(action) {
-            if *turns > 1 as i8 {
-                return false;
+
+    /// Tries every split of the steps between touches, great strides and innovation
+    fn quality_gain(&self, key: &BoundKey) -> f32 {
+        let Some(touch_cp_cost) = self.min_cp_cost else {
+            return 0.0;
+        };
+        // quick innovation counts as at most one free innovation
+        let free_innovations = key.quick_innovation as u32;
+        let mut best = 0.0;
+        for touches in 0..=key.quality_steps {
+            let cp_left = key.cp - touches as i32 * touch_cp_cost;
+            if cp_left < 0 {
+                break;
+            }
+            let buff_steps = key.quality_steps - touches;
+            let paid_innovations = match self.innovation_cp_cost {
+                Some(cp_cost) => (cp_left / cp_cost) as u32,
+                None => 0,
+            };
+            let innovations = buff_steps.min(free_innovations + paid_innovations);
+            for innovation in 0..=innovations {
+                let paid = innovation.saturating_sub(free_innovations);
+                let cp_left = cp_left - paid as i32 * self.innovation_cp_cost.unwrap_or(0);
+                let innovation_turns = paid * 4 + (innovation - paid);
+                let great_strides = if self.great_strides {
+                    (buff_steps - innovation)
+                        .min((cp_left / Action::GreatStrides.details().cp_cost) as u32)
+                } else {
+                    0
+                };
+                let gain = self.touch_gain(
+                    key,
+                    touches,
+                    great_strides as i32 + key.great_strides as i32,
+                    innovation_turns as i32 + key.innovation_turns,
+                );
+                if gain > best {
+                    best = gain;
+                }
             }
         }
+        best
     }
 
-    if state.step >= state.synth.max_length {
-        return false;
+    /// Quality from `touches` touches, with the buffs landing on the touches with the most inner quiet
+    fn touch_gain(&self, key: &BoundKey, touches: u32, great_strides: i32, innovation: i32) -> f32 {
+        let mut gain = 0.0;
+        for touch in 0..touches as i32 {
+            let stacks = (key.stacks + touch * self.inner_quiet_gain).min(10);
+            let from_last = touches as i32 - touch;
+            let mut buffs = 1.0;
+            if from_last <= great_strides {
+                buffs += 1.0;
+            }
+            if from_last <= innovation {
+                buffs += 0.5;
+            }
+            let mut multiplier = self.touch_multiplier;
+            if from_last == 1 && self.byregots_blessing {
+                multiplier = multiplier.max(1.0 + 0.2 * stacks as f32);
+            }
+            gain += self.base_quality * multiplier * buffs * (1.0 + 0.1 * stacks as f32);
+        }
+        gain * self.condition_multiplier
     }
+}
+
+/// Children of a state still left to try, the next one to try last
+type Children<'a> = Vec<(Action, State<'a>, SimulationCondition)>;
 
-    true
+/// Depth first branch and bound over the crafter's actions.
+/// The stack is kept between calls to [Search::run], so the search can be paused and picked up again.
+struct Search {
+    actions: Vec<Action>,
+    max_length: usize,
+    bounds: Bounds,
+    best: Option<Best>,
+    path: Vec<Action>,
+    /// Actions left to try at each depth, the state they start from is found by replaying `path`
+    pending: Vec<Vec<Action>>,
+    /// Shortest path length each state has been reached with
+    seen: HashMap<StateKey, usize>,
 }
 
+impl Search {
+    fn new(synth: &Synth) -> Self {
+        // only rely on full success for solver
+        let actions: Vec<Action> = synth
+            .crafter
+            .actions
+            .iter()
+            .copied()
+            .filter(|a| a.details().success_probability >= 1.0)
+            .collect();
+        let max_length = match synth.max_length {
+            0 => usize::MAX,
+            max_length => max_length as usize,
+        };
+        let mut search = Self {
+            bounds: Bounds::new(synth, &actions),
+            actions,
+            max_length,
+            best: None,
+            path: vec![],
+            pending: vec![],
+            seen: HashMap::new(),
+        };
+        let root = search.expand(
+            &State::from(synth),
+            &SimulationCondition::new_sim_condition(synth),
+        );
+        search
+            .pending
+            .push(root.into_iter().map(|(action, ..)| action).collect());
+        search
+    }
 
-fn compare_actions<'r, 's>((a_actions, a_state): &'r (Vec<Action>, State<'_>), (b_actions, b_state): &'s (Vec<Action>, State<'_>)) -> Ordering {
-    // assume all states complete progress
-    if a_state.quality_state >= a_state.synth.recipe.max_quality as i32
-        && b_state.quality_state >= a_state.synth.recipe.max_quality as i32
-    {
-        // both states have max quality, so check if one has less steps
-        a_state.step.cmp(&b_state.step)
-    } else {
-        // both qualities aren't complete, choose the higher quality synth
-        a_state.quality_state.cmp(&b_state.quality_state)
+    /// True if nothing below this state can beat the best solution found so far
+    fn can_prune(&mut self, state: &State<'_>) -> bool {
+        let steps_left = self
+            .max_length
+            .saturating_sub(self.path.len())
+            .min(self.bounds.max_steps(state) as usize) as u32;
+        let Some(max_quality) = self.bounds.max_quality(state, steps_left) else {
+            return true;
+        };
+        match &self.best {
+            None => false,
+            // finishing takes at least one more step, so equal quality can't be shorter
+            Some(best) => {
+                max_quality < best.quality
+                    || (max_quality == best.quality && self.path.len() + 1 >= best.actions.len())
+            }
+        }
     }
-}
 
-/// internal worker that recursively checks tries each possible action
-#[cfg(feature = "thread")]
-fn better_options<'a>(
-    state: State<'a>,
-    synth: &'a Synth,
-) -> Option<(Vec<Action>, State<'a>)> {
-    // advance over iter
-    synth
-        .crafter
-        .actions
-        .par_iter()
-        // filtering out actions that don't have any effect can help the most
-        .filter(|action| {
-            filter_usable(**action, &state)
-        })
-        .flat_map(|a| {
-            add_action(*a, &state, synth)
-        })
-        .max_by(compare_actions)
-}
+    /// Records a finished craft if it's valid and beats the best one so far
+    fn finish(&mut self, state: &State<'_>) {
+        let violations = state.check_violations();
+        if !(violations.durability_ok && violations.cp_ok && violations.trick_ok) {
+            return;
+        }
+        let quality = state
+            .quality_state
+            .min(state.synth.recipe.max_quality as i32);
+        let better = match &self.best {
+            None => true,
+            Some(best) => {
+                quality > best.quality
+                    || (quality == best.quality && self.path.len() < best.actions.len())
+            }
+        };
+        if better {
+            self.best = Some(Best {
+                quality,
+                actions: self.path.clone(),
+            });
+        }
+    }
 
-/// internal worker that recursively checks tries each possible action
-#[cfg(not(feature = "thread"))]
-fn better_options<'a>(
-    state: State<'a>,
-    synth: &'a Synth,
-) -> Option<(Vec<Action>, State<'a>)> {
-    // advance over iter
-    synth
-        .crafter
-        .actions
-        .iter()
-        // filtering out actions that don't have any effect can help the most
-        .filter(|action| {
-            filter_usable(**action, &state)
-        })
-        .flat_map(|a| {
-            add_action(*a, &state, synth)
-        })
-        .max_by(compare_actions)
+    /// Children of the state at the end of the path worth trying, empty if the state can be pruned
+    fn expand<'a>(&mut self, state: &State<'a>, condition: &SimulationCondition) -> Children<'a> {
+        if self.path.len() >= self.max_length || self.can_prune(state) {
+            return vec![];
+        }
+        match self.seen.get(&StateKey::from(state)) {
+            Some(length) if *length <= self.path.len() => return vec![],
+            _ => {
+                self.seen.insert(StateKey::from(state), self.path.len());
+            }
+        }
+
+        // reversed, so the stable sort below leaves ties in the crafter's order once popped
+        let mut children: Children<'a> = self
+            .actions
+            .iter()
+            .rev()
+            .filter_map(|action| {
+                let mut condition = condition.clone();
+                let new_state = state.add_action(*action, &mut condition);
+                // wasted actions for this are pointless.
+                if new_state.wasted_actions > state.wasted_actions
+                    || new_state.cp_state < 0
+                    || new_state.trick_uses > state.synth.max_trick_uses
+                {
+                    return None;
+                }
+                Some((*action, new_state, condition))
+            })
+            .collect();
+        // try the highest quality first, so good solutions show up early and prune more
+        children.sort_by(|(_, a, _), (_, b, _)| {
+            a.quality_state
+                .cmp(&b.quality_state)
+                .then(a.progress_state.cmp(&b.progress_state))
+        });
+        children
+    }
+
+    /// Visits up to `budget` more nodes, returns true once the whole tree has been searched
+    fn run(&mut self, synth: &Synth, mut budget: usize) -> bool {
+        // rebuild the states along the path, only the actions are kept between calls
+        let mut state = State::from(synth);
+        let mut condition = SimulationCondition::new_sim_condition(synth);
+        let mut stack: Vec<Children<'_>> = Vec::with_capacity(self.pending.len());
+        for (depth, actions) in self.pending.drain(..).enumerate() {
+            stack.push(
+                actions
+                    .into_iter()
+                    .map(|action| {
+                        let mut condition = condition.clone();
+                        let new_state = state.add_action(action, &mut condition);
+                        (action, new_state, condition)
+                    })
+                    .collect(),
+            );
+            if let Some(action) = self.path.get(depth) {
+                state = state.add_action(*action, &mut condition);
+            }
+        }
+
+        while budget > 0 {
+            let Some(children) = stack.last_mut() else {
+                return true;
+            };
+            let Some((action, new_state, condition)) = children.pop() else {
+                stack.pop();
+                self.path.pop();
+                continue;
+            };
+            budget -= 1;
+            self.path.push(action);
+            if new_state.progress_state >= synth.recipe.difficulty as i32 {
+                self.finish(&new_state);
+            } else if new_state.durability_state > 0 {
+                let children = self.expand(&new_state, &condition);
+                stack.push(children);
+                continue;
+            }
+            self.path.pop();
+        }
+
+        self.pending = stack
+            .into_iter()
+            .map(|children| children.into_iter().map(|(action, ..)| action).collect())
+            .collect();
+        self.pending.is_empty()
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::solver::find_solution;
-    use crate::xiv_model::Synth;
+    use crate::actions::Action;
+    use crate::simulation::simulate_sequence;
+    use crate::solver::{find_solution, Search, StateKey};
+    use crate::xiv_model::{SimulationCondition, State, Synth};
+
+    const SMOL_ABILITY: &str = r#"{"crafter":{"level":9,"craftsmanship":100,"control":100,"cp":180,"actions":["basicSynth","basicTouch","mastersMend"]},"recipe":{"baseLevel":10,"difficulty":45,"durability":60,"level":10,"maxQuality":250,"progressDivider":50,"progressModifier":100,"qualityDivider":30,"qualityModifier":100,"suggestedControl":29,"suggestedCraftsmanship":59,"name":"Heat Vent Component","cls":"Culinarian","startQuality":0,"safetyMargin":0},"sequence":[],"algorithm":"eaComplex","maxTricksUses":0,"maxMontecarloRuns":400,"reliabilityPercent":100,"useConditions":false,"maxLength":8,"solver":{"algorithm":"eaComplex","penaltyWeight":10000,"population":10000,"subPopulations":10,"solveForCompletion":false,"remainderCPFitnessValue":10,"remainderDurFitnessValue":100,"maxStagnationCounter":25,"generations":1000},"debug":true}"#;
+    const LVL_50_SYNTH: &str = r#"{"crafter":{"level":51,"craftsmanship":117,"control":158,"cp":180,"actions":["basicSynth2","basicTouch","standardTouch","byregotsBlessing","tricksOfTheTrade","mastersMend","wasteNot","wasteNot2","veneration","greatStrides","innovation","observe"]},"recipe":{"cls":"Culinarian","level":40,"difficulty":138,"durability":60,"startQuality":0,"maxQuality":3500,"baseLevel":40,"progressDivider":50,"progressModifier":100,"qualityDivider":30,"qualityModifier":100,"suggestedControl":68,"suggestedCraftsmanship":136,"name":"Grade 4 Skybuilders' Sesame Cookie","safetyMargin":0},"sequence":[],"algorithm":"eaComplex","maxTricksUses":0,"maxMontecarloRuns":400,"reliabilityPercent":100,"useConditions":false,"maxLength":0,"solver":{"algorithm":"eaComplex","penaltyWeight":10000,"population":10000,"subPopulations":10,"solveForCompletion":false,"remainderCPFitnessValue":10,"remainderDurFitnessValue":100,"maxStagnationCounter":25,"generations":1000},"debug":true}"#;
 
-    const TEST_SYNTH: &str = r#"{"crafter":{"level":78,"craftsmanship":863,"control":877,"cp":412,"actions":["muscleMemory","reflect","basicSynth2","carefulSynthesis","groundwork","intensiveSynthesis","delicateSynthesis","basicTouch","standardTouch","byregotsBlessing","preciseTouch","prudentTouch","preparatoryTouch","tricksOfTheTrade","mastersMend","wasteNot","wasteNot2","veneration","greatStrides","innovation","finalAppraisal","observe"]},"recipe":{"cls":"Weaver","level":390,"difficulty":1195,"durability":60,"startQuality":0,"safetyMargin":0,"maxQuality":3010,"baseLevel":71,"progressDivider":101,"progressModifier":100,"qualityDivider":81,"qualityModifier":100,"suggestedControl":1220,"suggestedCraftsmanship":1320,"name":"Custom Gathering Tool Components"},"sequence":[],"algorithm":"eaComplex","maxTricksUses":0,"maxMontecarloRuns":400,"reliabilityPercent":100,"useConditions":false,"maxLength":50,"solver":{"algorithm":"eaComplex","penaltyWeight":10000,"population":12000,"subPopulations":10,"solveForCompletion":false,"remainderCPFitnessValue":10,"remainderDurFitnessValue":100,"maxStagnationCounter":25,"generations":2000},"debug":true}"#;
+    /// Quality of the sequence if it finishes the craft
+    fn finished_quality(synth: &Synth, actions: &[Action]) -> Option<i32> {
+        let steps = simulate_sequence(synth, actions);
+        let last = steps.last().unwrap();
+        let finished = last.violations.progress_ok
+            && last.violations.durability_ok
+            && last.violations.cp_ok
+            // the craft ends as soon as progress is complete
+            && steps[..steps.len() - 1].iter().all(|s| !s.violations.progress_ok);
+        finished.then_some(last.quality.min(synth.recipe.max_quality as i32))
+    }
 
     #[test]
-    fn test() {
-        let synth: Synth = serde_json::from_str(TEST_SYNTH).unwrap();
+    fn matches_brute_force() {
+        let synth: Synth = serde_json::from_str(SMOL_ABILITY).unwrap();
         let solution = find_solution(&synth);
-        assert!(solution.len() > 0);
-        assert_eq!(solution, vec![]);
-        eprintln!("{solution:?}");
+        let quality = finished_quality(&synth, &solution).unwrap();
+        assert!(solution.len() <= 8);
+
+        // enumerate every sequence up to the max length
+        let actions = &synth.crafter.actions;
+        let mut best = 0;
+        let mut sequences: Vec<Vec<Action>> = vec![vec![]];
+        for _ in 0..synth.max_length {
+            sequences = sequences
+                .iter()
+                .flat_map(|s| {
+                    actions.iter().map(move |a| {
+                        let mut s = s.clone();
+                        s.push(*a);
+                        s
+                    })
+                })
+                .collect();
+            for sequence in &sequences {
+                if let Some(q) = finished_quality(&synth, sequence) {
+                    best = best.max(q);
+                }
+            }
+        }
+        assert_eq!(quality, best);
+    }
+
+    #[test]
+    fn lvl50_synth() {
+        let mut synth: Synth = serde_json::from_str(LVL_50_SYNTH).unwrap();
+        synth.max_length = 10;
+        let solution = find_solution(&synth);
+        assert!(!solution.is_empty());
+        assert!(solution.len() <= 10);
+        assert!(finished_quality(&synth, &solution).unwrap() > 0);
+    }
+
+    #[test]
+    fn resumes_where_it_stopped() {
+        let mut synth: Synth = serde_json::from_str(LVL_50_SYNTH).unwrap();
+        synth.max_length = 8;
+        let mut search = Search::new(&synth);
+        let mut steps = 1;
+        while !search.run(&synth, 100) {
+            steps += 1;
+        }
+        assert!(steps > 1);
+        let best = search.best.map(|best| best.actions).unwrap();
+        assert_eq!(best, find_solution(&synth));
+    }
+
+    #[test]
+    fn no_length_limit() {
+        let mut synth: Synth = serde_json::from_str(SMOL_ABILITY).unwrap();
+        synth.max_length = 0;
+        let limited = find_solution(&Synth {
+            max_length: 8,
+            ..synth.clone()
+        });
+        assert_eq!(
+            finished_quality(&synth, &find_solution(&synth)),
+            finished_quality(&synth, &limited)
+        );
+
+        // no quality actions to bound the search by
+        synth.crafter.actions = vec![Action::BasicSynth, Action::MastersMend];
+        let solution = find_solution(&synth);
+        assert!(finished_quality(&synth, &solution).is_some());
+
+        // quick innovation doesn't cost any cp
+        synth.crafter.actions = vec![
+            Action::BasicSynth,
+            Action::BasicTouch,
+            Action::QuickInnovation,
+        ];
+        let solution = find_solution(&synth);
+        assert!(finished_quality(&synth, &solution).unwrap() > 0);
+    }
+
+    /// Runs the sequence without checking anything
+    fn run<'a>(synth: &'a Synth, actions: &[Action]) -> State<'a> {
+        let mut condition = SimulationCondition::new_sim_condition(synth);
+        actions.iter().fold(State::from(synth), |state, action| {
+            state.add_action(*action, &mut condition)
+        })
+    }
+
+    #[test]
+    fn tricks_left() {
+        let mut synth: Synth = serde_json::from_str(LVL_50_SYNTH).unwrap();
+        synth.max_trick_uses = 1;
+        synth.crafter.craft_points = 80;
+        synth.recipe.difficulty = 20;
+        synth.max_length = 6;
+        synth.crafter.actions = vec![
+            Action::BasicSynth2,
+            Action::BasicTouch,
+            Action::StandardTouch,
+            Action::TricksOfTheTrade,
+            Action::GreatStrides,
+            Action::Observe,
+            Action::ByregotsBlessing,
+        ];
+        // most of the cp from tricks is lost to the cap, so both end on the same cp, quality and buffs
+        let spent = run(
+            &synth,
+            &[
+                Action::BasicTouch,
+                Action::TricksOfTheTrade,
+                Action::GreatStrides,
+                Action::GreatStrides,
+            ],
+        );
+        let kept = run(
+            &synth,
+            &[
+                Action::BasicTouch,
+                Action::Observe,
+                Action::Observe,
+                Action::GreatStrides,
+            ],
+        );
+        assert_eq!(spent.cp_state, kept.cp_state);
+        assert_eq!(spent.quality_state, kept.quality_state);
+        // only one of them can still use tricks, the search can't treat them as the same state
+        assert!(StateKey::from(&spent) != StateKey::from(&kept));
+    }
+
+    #[test]
+    fn respects_trick_uses() {
+        let synth: Synth = serde_json::from_str(LVL_50_SYNTH).unwrap();
+        assert_eq!(synth.max_trick_uses, 0);
+        let solution = find_solution(&Synth {
+            max_length: 10,
+            ..synth
+        });
+        assert!(!solution.contains(&Action::TricksOfTheTrade));
     }
 }
@@ -0,0 +1,506 @@
+use crate::actions::Action;
+use crate::genome::try_genome_from_actions;
+use crate::hq::hq_percent;
+use crate::simulator::{CalcState, CrafterActions};
+use crate::solver::find_solution;
+use crate::xiv_model::Synth;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::JsValue;
+
+/// Times the short stats are doubled looking for a starting point when the crafter's own stats aren't enough
+const MAX_DOUBLINGS: u32 = 4;
+/// Passes over the stats, lowering one can let another go lower
+const MAX_PASSES: u32 = 4;
+
+/// Crafter stats the stat finder works with
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CrafterStats {
+    pub craftsmanship: u32,
+    pub control: u32,
+    pub cp: u32,
+}
+
+/// Stats to hold at a set value while the others are searched, `None` leaves the stat free
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct FixedStats {
+    pub craftsmanship: Option<u32>,
+    pub control: Option<u32>,
+    pub cp: Option<u32>,
+}
+
+/// One of the crafter's stats
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Stat {
+    Craftsmanship,
+    Control,
+    Cp,
+}
+
+impl CrafterStats {
+    fn get(&self, stat: Stat) -> u32 {
+        match stat {
+            Stat::Craftsmanship => self.craftsmanship,
+            Stat::Control => self.control,
+            Stat::Cp => self.cp,
+        }
+    }
+
+    fn set(&mut self, stat: Stat, value: u32) {
+        match stat {
+            Stat::Craftsmanship => self.craftsmanship = value,
+            Stat::Control => self.control = value,
+            Stat::Cp => self.cp = value,
+        }
+    }
+}
+
+impl FixedStats {
+    fn get(&self, stat: Stat) -> Option<u32> {
+        match stat {
+            Stat::Craftsmanship => self.craftsmanship,
+            Stat::Control => self.control,
+            Stat::Cp => self.cp,
+        }
+    }
+}
+
+/// The synth with the crafter's stats swapped out
+pub(crate) fn with_stats(synth: &Synth, stats: CrafterStats) -> Synth {
+    let mut synth = synth.clone();
+    synth.crafter.craftsmanship = stats.craftsmanship;
+    synth.crafter.control = stats.control;
+    synth.crafter.craft_points = stats.cp;
+    synth
+}
+
+/// Stats that would get the rotation closer to the target, empty when it gets there
+/// or when it fails in a way stats can't fix, like running out of durability.
+fn shortfalls(synth: &Synth, genome: &CrafterActions, stats: CrafterStats) -> Vec<Stat> {
+    let synth = with_stats(synth, stats);
+    let state = genome.calculate_final_state(&synth, &mut None);
+    let violations = state.check_violations();
+    let mut shortfalls = vec![];
+    // the simulation stops before an action it can't pay for, so running out of cp looks like missing progress
+    let out_of_cp = state.actions_used() < genome.len() && state.durability_state > 0;
+    if !violations.cp_ok || (out_of_cp && !violations.progress_ok) {
+        shortfalls.push(Stat::Cp);
+    } else if !violations.progress_ok {
+        shortfalls.push(Stat::Craftsmanship);
+    }
+    if state.quality_state < synth.target_quality() as i32 {
+        shortfalls.push(Stat::Control);
+    }
+    shortfalls
+}
+
+/// True if the rotation finishes the craft at the target quality with these stats
+fn reaches_target(synth: &Synth, genome: &CrafterActions, stats: CrafterStats) -> bool {
+    let synth = with_stats(synth, stats);
+    let state = genome.calculate_final_state(&synth, &mut None);
+    let violations = state.check_violations();
+    violations.progress_ok
+        && violations.durability_ok
+        && violations.cp_ok
+        && state.quality_state >= synth.target_quality() as i32
+}
+
+/// Lowers craftsmanship, control and cp as far as the rotation still completes the craft at max quality,
+/// or the target tier's quality when the solver has one.
+/// Stats in `fixed` keep their value, the others start from the crafter's stats and are bisected one at a time.
+/// The result always passes, but it isn't always the lowest passing value, see the bisection below.
+/// Returns `None` if the rotation can't reach the target even with the free stats raised well past the crafter's,
+/// or if it uses an action the crafter doesn't have.
+pub fn find_minimum_stats(
+    synth: &Synth,
+    actions: &[Action],
+    fixed: FixedStats,
+) -> Option<CrafterStats> {
+    let genome = try_genome_from_actions(synth, actions)?;
+    let stats = [Stat::Craftsmanship, Stat::Control, Stat::Cp];
+    let free: Vec<Stat> = stats
+        .into_iter()
+        .filter(|stat| fixed.get(*stat).is_none())
+        .collect();
+    let mut best = CrafterStats {
+        craftsmanship: fixed.craftsmanship.unwrap_or(synth.crafter.craftsmanship),
+        control: fixed.control.unwrap_or(synth.crafter.control),
+        cp: fixed.cp.unwrap_or(synth.crafter.craft_points),
+    };
+
+    let mut doublings = 0;
+    while !reaches_target(synth, &genome, best) {
+        let raise: Vec<Stat> = shortfalls(synth, &genome, best)
+            .into_iter()
+            .filter(|stat| free.contains(stat))
+            .collect();
+        if doublings == MAX_DOUBLINGS || raise.is_empty() {
+            return None;
+        }
+        // only the stats that are short, raising craftsmanship could finish the craft before the touches
+        for stat in raise {
+            best.set(stat, (best.get(stat) * 2).max(1));
+        }
+        doublings += 1;
+    }
+
+    // more control and cp only ever make the craft easier, more craftsmanship can also finish the craft
+    // before the touches land, so for it the bisection finds a passing value rather than the lowest one
+    for _ in 0..MAX_PASSES {
+        let before = best;
+        for stat in &free {
+            let (mut low, mut high) = (0, best.get(*stat));
+            while low < high {
+                let middle = low + (high - low) / 2;
+                let mut candidate = best;
+                candidate.set(*stat, middle);
+                if reaches_target(synth, &genome, candidate) {
+                    high = middle;
+                } else {
+                    low = middle + 1;
+                }
+            }
+            best.set(*stat, high);
+        }
+        if best == before {
+            break;
+        }
+    }
+    Some(best)
+}
+
+/// Values a stat takes in a sweep, `from` to `to` inclusive
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct StatRange {
+    pub stat: Stat,
+    pub from: u32,
+    pub to: u32,
+    /// Distance between values, 1 shows every breakpoint
+    #[serde(default = "default_step")]
+    pub step: u32,
+}
+
+fn default_step() -> u32 {
+    1
+}
+
+impl StatRange {
+    fn values(&self) -> impl Iterator<Item = u32> {
+        (self.from..=self.to).step_by(self.step.max(1) as usize)
+    }
+}
+
+/// Result of the rotation at one point of a sweep
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SweepRow {
+    pub stats: CrafterStats,
+    /// Actions used before the craft finished or broke
+    pub steps: u32,
+    pub progress: i32,
+    pub quality: i32,
+    pub hq_percent: u32,
+    /// Finished the craft without running out of durability or cp
+    pub feasible: bool,
+}
+
+/// Table of how a rotation does as one or two stats change
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SweepReport {
+    pub sequence: Vec<Action>,
+    /// The first stat changes fastest, a row follows the one before it unless the second stat moved on
+    pub rows: Vec<SweepRow>,
+}
+
+impl SweepReport {
+    /// Rows where the steps or feasibility changed from the row before with the same other stats,
+    /// e.g. the craftsmanship where one more point saves a step
+    pub fn breakpoints(&self) -> Vec<&SweepRow> {
+        self.rows
+            .windows(2)
+            .filter(|pair| {
+                let (before, row) = (&pair[0], &pair[1]);
+                let differing = [
+                    before.stats.craftsmanship != row.stats.craftsmanship,
+                    before.stats.control != row.stats.control,
+                    before.stats.cp != row.stats.cp,
+                ];
+                differing.iter().filter(|d| **d).count() == 1
+                    && (before.steps != row.steps || before.feasible != row.feasible)
+            })
+            .map(|pair| &pair[1])
+            .collect()
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv =
+            "craftsmanship,control,cp,steps,progress,quality,hqPercent,feasible\n".to_string();
+        for row in &self.rows {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{}",
+                row.stats.craftsmanship,
+                row.stats.control,
+                row.stats.cp,
+                row.steps,
+                row.progress,
+                row.quality,
+                row.hq_percent,
+                row.feasible
+            );
+        }
+        csv
+    }
+}
+
+/// Runs the rotation at every combination of the swept stats, the other stats stay at the crafter's.
+/// An empty `actions` sweeps the rotation [find_solution] finds at the crafter's own stats, which needs a `maxLength`.
+/// Returns `None` if the rotation uses an action the crafter doesn't have, or there's no rotation and no `maxLength`.
+pub fn sweep_stats(
+    synth: &Synth,
+    actions: &[Action],
+    first: StatRange,
+    second: Option<StatRange>,
+) -> Option<SweepReport> {
+    let sequence = if actions.is_empty() {
+        // an unbounded search runs for far too long to sit behind a table
+        if synth.max_length == 0 {
+            return None;
+        }
+        find_solution(synth)
+    } else {
+        actions.to_vec()
+    };
+    let genome = try_genome_from_actions(synth, &sequence)?;
+    let crafter = CrafterStats {
+        craftsmanship: synth.crafter.craftsmanship,
+        control: synth.crafter.control,
+        cp: synth.crafter.craft_points,
+    };
+    let outer: Vec<Option<u32>> = match second {
+        Some(range) => range.values().map(Some).collect(),
+        None => vec![None],
+    };
+    let mut rows = vec![];
+    for outer_value in outer {
+        for value in first.values() {
+            let mut stats = crafter;
+            if let (Some(range), Some(outer_value)) = (second, outer_value) {
+                stats.set(range.stat, outer_value);
+            }
+            stats.set(first.stat, value);
+            let synth = with_stats(synth, stats);
+            let state = genome.calculate_final_state(&synth, &mut None);
+            let violations = state.check_violations();
+            let feasible = violations.progress_ok && violations.durability_ok && violations.cp_ok;
+            rows.push(SweepRow {
+                stats,
+                steps: state.actions_used() as u32,
+                progress: state.progress_state,
+                quality: state.quality_state,
+                // a failed craft comes out normal quality however much quality it had
+                hq_percent: if feasible {
+                    hq_percent(state.quality_state, synth.recipe.max_quality)
+                } else {
+                    0
+                },
+                feasible,
+            });
+        }
+    }
+    Some(SweepReport { sequence, rows })
+}
+
+#[wasm_bindgen]
+pub fn find_minimum_stats_wasm(synth: JsValue, actions: JsValue, fixed: JsValue) -> JsValue {
+    console_error_panic_hook::set_once();
+    let synth: Synth = serde_wasm_bindgen::from_value(synth).unwrap();
+    let actions: Vec<Action> = serde_wasm_bindgen::from_value(actions).unwrap();
+    let fixed: FixedStats = serde_wasm_bindgen::from_value(fixed).unwrap_or_default();
+    serde_wasm_bindgen::to_value(&find_minimum_stats(&synth, &actions, fixed)).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn sweep_stats_wasm(
+    synth: JsValue,
+    actions: JsValue,
+    first: JsValue,
+    second: JsValue,
+) -> JsValue {
+    console_error_panic_hook::set_once();
+    let synth: Synth = serde_wasm_bindgen::from_value(synth).unwrap();
+    let actions: Vec<Action> = serde_wasm_bindgen::from_value(actions).unwrap();
+    let first: StatRange = serde_wasm_bindgen::from_value(first).unwrap();
+    let second: Option<StatRange> = serde_wasm_bindgen::from_value(second).unwrap();
+    serde_wasm_bindgen::to_value(&sweep_stats(&synth, &actions, first, second)).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::actions::Action;
+    use crate::genome::genome_from_actions;
+    use crate::stats::{
+        find_minimum_stats, reaches_target, sweep_stats, CrafterStats, FixedStats, Stat, StatRange,
+    };
+    use crate::xiv_model::Synth;
+
+    const CRAFTER_SYNTH: &str = r#"{"crafter":{"level":78,"craftsmanship":863,"control":877,"cp":412,"actions":["muscleMemory","reflect","basicSynth2","carefulSynthesis","groundwork","intensiveSynthesis","delicateSynthesis","basicTouch","standardTouch","byregotsBlessing","preciseTouch","prudentTouch","preparatoryTouch","tricksOfTheTrade","mastersMend","wasteNot","wasteNot2","veneration","greatStrides","innovation","finalAppraisal","observe"]},"recipe":{"cls":"Weaver","level":390,"difficulty":1195,"durability":60,"startQuality":0,"safetyMargin":0,"maxQuality":3010,"baseLevel":71,"progressDivider":101,"progressModifier":100,"qualityDivider":81,"qualityModifier":100,"suggestedControl":1220,"suggestedCraftsmanship":1320,"name":"Custom Gathering Tool Components"},"sequence":[],"algorithm":"eaComplex","maxTricksUses":0,"maxMontecarloRuns":400,"reliabilityPercent":100,"useConditions":false,"maxLength":50,"solver":{"algorithm":"eaComplex","penaltyWeight":10000,"population":200000,"subPopulations":10,"solveForCompletion":false,"remainderCPFitnessValue":10,"remainderDurFitnessValue":100,"maxStagnationCounter":25,"generations":2000},"debug":true}"#;
+
+    const ROTATION: [Action; 14] = [
+        Action::MuscleMemory,
+        Action::Veneration,
+        Action::Groundwork,
+        Action::Innovation,
+        Action::PreparatoryTouch,
+        Action::MastersMend,
+        Action::PreparatoryTouch,
+        Action::GreatStrides,
+        Action::Innovation,
+        Action::ByregotsBlessing,
+        Action::MastersMend,
+        Action::Veneration,
+        Action::Groundwork,
+        Action::CarefulSynthesis,
+    ];
+
+    #[test]
+    fn finds_breakpoints() {
+        let synth: Synth = serde_json::from_str(CRAFTER_SYNTH).unwrap();
+        let genome = genome_from_actions(&synth, &ROTATION);
+        let stats = find_minimum_stats(&synth, &ROTATION, FixedStats::default()).unwrap();
+        assert!(reaches_target(&synth, &genome, stats));
+        // one point lower in any stat and the rotation falls short
+        for lower in [
+            CrafterStats {
+                craftsmanship: stats.craftsmanship - 1,
+                ..stats
+            },
+            CrafterStats {
+                control: stats.control - 1,
+                ..stats
+            },
+            CrafterStats {
+                cp: stats.cp - 1,
+                ..stats
+            },
+        ] {
+            assert!(!reaches_target(&synth, &genome, lower));
+        }
+    }
+
+    #[test]
+    fn holds_fixed_stats() {
+        let synth: Synth = serde_json::from_str(CRAFTER_SYNTH).unwrap();
+        let free = find_minimum_stats(&synth, &ROTATION, FixedStats::default()).unwrap();
+        let fixed = FixedStats {
+            cp: Some(500),
+            ..Default::default()
+        };
+        let stats = find_minimum_stats(&synth, &ROTATION, fixed).unwrap();
+        assert_eq!(stats.cp, 500);
+        assert_eq!(stats.control, free.control);
+
+        // not enough cp for the rotation and it can't be raised
+        let fixed = FixedStats {
+            cp: Some(10),
+            ..Default::default()
+        };
+        assert_eq!(find_minimum_stats(&synth, &ROTATION, fixed), None);
+    }
+
+    #[test]
+    fn sweep_needs_a_length_limit() {
+        let mut synth: Synth = serde_json::from_str(CRAFTER_SYNTH).unwrap();
+        let range = StatRange {
+            stat: Stat::Cp,
+            from: 300,
+            to: 400,
+            step: 100,
+        };
+        // nothing to sweep and no limit on the search that would find a rotation
+        synth.max_length = 0;
+        assert_eq!(sweep_stats(&synth, &[], range, None), None);
+    }
+
+    #[test]
+    fn sweeps_craftsmanship() {
+        let synth: Synth = serde_json::from_str(CRAFTER_SYNTH).unwrap();
+        let rotation = [
+            Action::MuscleMemory,
+            Action::Veneration,
+            Action::Groundwork,
+            Action::Groundwork,
+            Action::CarefulSynthesis,
+        ];
+        let range = StatRange {
+            stat: Stat::Craftsmanship,
+            from: 500,
+            to: 1000,
+            step: 1,
+        };
+        let report = sweep_stats(&synth, &rotation, range, None).unwrap();
+        assert_eq!(report.rows.len(), 501);
+        assert!(report.rows.iter().all(|row| row.stats.control == 877));
+        // more craftsmanship never takes more steps
+        assert!(report.rows.windows(2).all(|p| p[1].steps <= p[0].steps));
+        assert!(!report.rows[0].feasible);
+        assert!(report
+            .rows
+            .iter()
+            .all(|row| row.feasible || row.hq_percent == 0));
+        let breakpoints = report.breakpoints();
+        // one where the craft starts finishing and one where the last careful synthesis isn't needed
+        assert_eq!(breakpoints.len(), 2);
+        for row in breakpoints {
+            let index = (row.stats.craftsmanship - 500) as usize;
+            let before = &report.rows[index - 1];
+            assert!(row.steps < before.steps || row.feasible != before.feasible);
+        }
+
+        let csv = report.to_csv();
+        assert_eq!(csv.lines().count(), 502);
+        assert!(csv.lines().nth(1).unwrap().starts_with("500,877,412,"));
+    }
+
+    #[test]
+    fn sweeps_two_stats() {
+        let synth: Synth = serde_json::from_str(CRAFTER_SYNTH).unwrap();
+        let first = StatRange {
+            stat: Stat::Control,
+            from: 800,
+            to: 900,
+            step: 50,
+        };
+        let second = StatRange {
+            stat: Stat::Cp,
+            from: 300,
+            to: 400,
+            step: 100,
+        };
+        let report = sweep_stats(&synth, &ROTATION, first, Some(second)).unwrap();
+        let stats: Vec<(u32, u32)> = report
+            .rows
+            .iter()
+            .map(|row| (row.stats.control, row.stats.cp))
+            .collect();
+        assert_eq!(
+            stats,
+            vec![
+                (800, 300),
+                (850, 300),
+                (900, 300),
+                (800, 400),
+                (850, 400),
+                (900, 400)
+            ]
+        );
+        let json = serde_json::to_string(&report).unwrap();
+        assert!(json.contains("\"hqPercent\""));
+    }
+}
@@ -1,14 +1,67 @@
-use crate::actions::{Action, ActionType};
+use crate::actions::{Action, ActionType, ACTION_COUNT};
+use crate::crossover::Crossover;
 use crate::effect_tracker::EffectData;
 use crate::level_table;
-use serde::{Deserialize, Serialize};
+use crate::mutator::MutationWeights;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 use crate::Action::{CarefulObservation, HeartAndSoul};
 use crate::level_table::level_table_lookup;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// The eight Disciples of the Hand, named the way the recipe data and the JS name them
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum CraftingClass {
+    Carpenter,
+    Blacksmith,
+    Armorer,
+    Goldsmith,
+    Leatherworker,
+    Weaver,
+    Alchemist,
+    Culinarian,
+}
+
+impl CraftingClass {
+    pub const ALL: [CraftingClass; 8] = [
+        CraftingClass::Carpenter,
+        CraftingClass::Blacksmith,
+        CraftingClass::Armorer,
+        CraftingClass::Goldsmith,
+        CraftingClass::Leatherworker,
+        CraftingClass::Weaver,
+        CraftingClass::Alchemist,
+        CraftingClass::Culinarian,
+    ];
+}
+
+impl Display for CraftingClass {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for CraftingClass {
+    type Err = String;
+
+    /// Parses a class name, ignoring case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        CraftingClass::ALL
+            .into_iter()
+            .find(|class| class.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown crafting class: {}", s))
+    }
+}
 
 #[derive(Deserialize, Serialize, Clone, Debug, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct Crafter {
+    #[serde(default)]
+    pub(crate) cls: Option<CraftingClass>,
     pub(crate) craftsmanship: u32,
     pub(crate) control: u32,
     #[serde(rename = "cp")]
@@ -20,9 +73,65 @@ pub struct Crafter {
     pub actions: Vec<Action>,
 }
 
+/// Stats for one class, the same shape as the JS profile's `crafterStats`
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ClassStats {
+    pub level: u32,
+    pub craftsmanship: u32,
+    pub control: u32,
+    pub cp: u32,
+    #[serde(default)]
+    pub specialist: bool,
+    #[serde(default)]
+    pub actions: Vec<Action>,
+}
+
+/// A crafter's stats for every class they've levelled, keyed by class like the JS profile
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(transparent)]
+pub struct CrafterProfile {
+    pub classes: BTreeMap<CraftingClass, ClassStats>,
+}
+
+impl CrafterProfile {
+    /// The crafter for one class, `None` if the profile doesn't have it
+    pub fn crafter(&self, cls: CraftingClass) -> Option<Crafter> {
+        let stats = self.classes.get(&cls)?;
+        Some(Crafter {
+            cls: Some(cls),
+            craftsmanship: stats.craftsmanship,
+            control: stats.control,
+            craft_points: stats.cp,
+            level: stats.level,
+            specialist: stats.specialist,
+            actions: stats.actions.clone(),
+        })
+    }
+}
+
+/// A name in every language the game data comes in
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct LocalizedName {
+    pub en: String,
+    pub de: String,
+    pub fr: String,
+    pub ja: String,
+}
+
+impl LocalizedName {
+    pub fn all(&self) -> [&str; 4] {
+        [&self.en, &self.de, &self.fr, &self.ja]
+    }
+}
+
 #[derive(Deserialize, Serialize, Clone, Debug, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct Recipe {
+    /// Class that crafts the recipe
+    #[serde(default)]
+    pub(crate) cls: Option<CraftingClass>,
     pub(crate) base_level: u32,
     pub(crate) level: u32,
     pub(crate) difficulty: u32,
@@ -37,18 +146,195 @@ pub struct Recipe {
     pub(crate) quality_divider: f32,
     pub(crate) quality_modifier: Option<u32>,
     pub(crate) stars: Option<u32>,
+    /// Collectability or quality thresholds for collectables and deliveries
+    #[serde(default)]
+    pub(crate) quality_tiers: Vec<QualityTier>,
+}
+
+/// A quality a collectable or delivery has to reach
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum QualityTier {
+    Quality(u32),
+    /// Collectability as shown in game, a tenth of the quality
+    Collectability(u32),
+}
+
+impl QualityTier {
+    pub fn quality(&self) -> u32 {
+        match self {
+            QualityTier::Quality(quality) => *quality,
+            QualityTier::Collectability(collectability) => collectability * 10,
+        }
+    }
+}
+
+impl Recipe {
+    /// Index of the highest tier `quality` reaches
+    pub(crate) fn tier_reached(&self, quality: i32) -> Option<usize> {
+        self.quality_tiers
+            .iter()
+            .enumerate()
+            .filter(|(_, tier)| quality >= tier.quality() as i32)
+            .max_by_key(|(_, tier)| tier.quality())
+            .map(|(index, _)| index)
+    }
+}
+
+/// Which solver backend the [crate::CraftSimulator] runs
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Algorithm {
+    /// Genetic algorithm, the JS's `eaSimple` is handled by the same backend
+    #[default]
+    #[serde(alias = "eaSimple")]
+    EaComplex,
+    /// Branch and bound search for the best sequence of actions that can't fail
+    Exhaustive,
+}
+
+/// Weights the genetic solver's fitness function uses, any missing field keeps the default
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct FitnessConfig {
+    /// Multiplier applied to the sum of violations (missing progress, broken durability, overspent cp...)
+    pub penalty_weight: f32,
+    /// Once the craft finishes at max quality the fitness is scaled by `1 + step_bonus / steps`,
+    /// so higher values prefer shorter macros
+    pub step_bonus: f32,
+    /// Fitness per leftover cp when solving for quality
+    pub leftover_cp_weight: f32,
+    /// Fitness per leftover durability when solving for quality
+    pub leftover_durability_weight: f32,
+    /// Fitness subtracted each time an action is used
+    pub action_costs: ActionCosts,
+    /// Score rotations over sampled conditions and success rolls instead of a single expected value pass
+    pub robust: Option<RobustFitness>,
+}
+
+/// Settings for scoring a rotation by running it through seeded Monte Carlo trials.
+/// The fitness comes from the success rate and the quality at `hqPercentile`,
+/// so a rotation that only does well on lucky rolls scores low.
+/// Every fitness evaluation simulates the rotation `trials` times, so the solver runs that many times slower;
+/// the local search at the end of a run uses the single expected value pass instead.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RobustFitness {
+    /// Number of trials every rotation is run through
+    pub trials: u32,
+    /// Percentile of the trials the HQ% is taken from, 10 scores a rotation by what 90% of its runs reach
+    pub hq_percentile: f32,
+    /// Every rotation uses the same trial seeds so its fitness doesn't change between generations
+    pub seed: u64,
+}
+
+impl Default for RobustFitness {
+    fn default() -> Self {
+        Self {
+            trials: 20,
+            hq_percentile: 10.0,
+            seed: 0,
+        }
+    }
+}
+
+/// Fitness cost of every action, a dense array so the fitness function doesn't hash each gene.
+/// Reads and writes as a map of action to cost, actions that aren't in the map are free.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActionCosts([f32; ACTION_COUNT]);
+
+impl ActionCosts {
+    pub fn get(&self, action: Action) -> f32 {
+        self.0[action as usize]
+    }
+}
+
+impl FromIterator<(Action, f32)> for ActionCosts {
+    fn from_iter<I: IntoIterator<Item = (Action, f32)>>(iter: I) -> Self {
+        let mut costs = Self([0.0; ACTION_COUNT]);
+        for (action, cost) in iter {
+            costs.0[action as usize] = cost;
+        }
+        costs
+    }
+}
+
+impl Serialize for ActionCosts {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        for (action, cost) in Action::ALL.iter().zip(self.0) {
+            if cost != 0.0 {
+                map.serialize_entry(action, &cost)?;
+            }
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for ActionCosts {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(HashMap::<Action, f32>::deserialize(deserializer)?
+            .into_iter()
+            .collect())
+    }
+}
+
+impl Default for FitnessConfig {
+    fn default() -> Self {
+        Self {
+            penalty_weight: 10000.0,
+            step_bonus: 4.0,
+            leftover_cp_weight: 0.0,
+            leftover_durability_weight: 0.0,
+            // crafters delineations cost more, bias towards macros that don't use it even if the user has it selected
+            action_costs: [(Action::HeartAndSoul, 1.0)].into_iter().collect(),
+            robust: None,
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct SolverVars {
+    #[serde(default)]
+    pub(crate) algorithm: Option<Algorithm>,
     pub(crate) solve_for_completion: bool,
     #[serde(rename = "remainderCPFitnessValue")]
     pub(crate) remainder_cp_fitness_value: i32,
     pub(crate) remainder_dur_fitness_value: i32,
     pub(crate) max_stagnation_counter: i32,
     pub(crate) population: i32,
+    /// Number of islands the population is split into
+    #[serde(default)]
+    pub(crate) sub_populations: u32,
+    /// Generations between each island sending its best individuals to the next one, 0 never migrates
+    #[serde(default = "default_migration_interval")]
+    pub(crate) migration_interval: u32,
+    /// How many of the best individuals move to the next island each migration
+    #[serde(default = "default_migrants")]
+    pub(crate) migrants: usize,
     pub(crate) generations: i32,
+    #[serde(default)]
+    pub(crate) mutation_weights: MutationWeights,
+    #[serde(default)]
+    pub(crate) crossover: Crossover,
+    /// Index into `recipe.qualityTiers` the solver aims for instead of max quality.
+    /// Once the tier is reached only the step bonus and leftover weights add to the fitness.
+    #[serde(default)]
+    pub(crate) target_tier: Option<usize>,
+    /// Keep every non dominated rotation and return them as a [crate::simulator::SimStep::ParetoFront]
+    #[serde(default)]
+    pub(crate) pareto_front: bool,
+    #[serde(flatten)]
+    pub(crate) fitness: FitnessConfig,
+}
+
+fn default_migration_interval() -> u32 {
+    10
+}
+
+fn default_migrants() -> usize {
+    2
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, Default)]
@@ -56,15 +342,65 @@ pub struct SolverVars {
 pub struct Synth {
     pub crafter: Crafter,
     pub(crate) recipe: Recipe,
-    #[serde(default)]
+    #[serde(default, alias = "maxTricksUses")]
     pub(crate) max_trick_uses: i32,
     pub(crate) reliability_percent: u32,
     pub(crate) max_length: u32,
+    #[serde(default)]
+    pub(crate) max_montecarlo_runs: u32,
+    #[serde(default)]
+    pub(crate) use_conditions: bool,
+    /// Rotation the solver starts from, part of the initial population is built from it
+    #[serde(default)]
+    pub(crate) sequence: Vec<Action>,
+    /// Seeds the solver so the same input always gives the same result
+    #[serde(default)]
+    pub(crate) seed: Option<u64>,
+    /// Same as `solver.algorithm`, the JS solver reads this one
+    #[serde(default)]
+    pub(crate) algorithm: Option<Algorithm>,
     #[serde(rename = "solver")]
     pub(crate) solver_vars: SolverVars,
 }
 
 impl Synth {
+    /// Builds a synth for the recipe with the profile's stats for the class that crafts it.
+    /// Settings outside the crafter, recipe and solver get the JS defaults.
+    /// `None` if the recipe has no class or the profile doesn't have that class.
+    pub fn from_profile(
+        profile: &CrafterProfile,
+        recipe: Recipe,
+        solver_vars: SolverVars,
+    ) -> Option<Synth> {
+        let crafter = profile.crafter(recipe.cls?)?;
+        Some(Synth {
+            crafter,
+            recipe,
+            reliability_percent: 100,
+            max_montecarlo_runs: 400,
+            solver_vars,
+            ..Default::default()
+        })
+    }
+
+    /// Backend the solver runs. Either `algorithm` or `solver.algorithm` can pick it, but they can't disagree
+    pub(crate) fn algorithm(&self) -> Result<Algorithm, String> {
+        match (self.algorithm, self.solver_vars.algorithm) {
+            (Some(top), Some(solver)) if top != solver => Err(format!(
+                "algorithm is {top:?} but solver.algorithm is {solver:?}"
+            )),
+            (top, solver) => Ok(top.or(solver).unwrap_or_default()),
+        }
+    }
+
+    /// Quality the solver is aiming for: the target tier if one is picked, otherwise max quality
+    pub(crate) fn target_quality(&self) -> u32 {
+        self.solver_vars
+            .target_tier
+            .and_then(|tier| self.recipe.quality_tiers.get(tier))
+            .map_or(self.recipe.max_quality, QualityTier::quality)
+    }
+
     pub(crate) fn calculate_progress_and_quality_increase(&self) -> (u32, u32) {
         let eff_crafter_level = level_table_lookup(self.crafter.level);
         let base_progress =
@@ -104,11 +440,11 @@ impl Synth {
 
 pub type AbilityMap = EffectData;
 
-#[derive(Debug, Serialize, Clone, Default)]
+#[derive(Debug, Serialize, Clone, Default, PartialEq, Eq, Hash)]
 #[serde(rename_all = "camelCase")]
 pub struct Effects {
-    count_downs: AbilityMap,
-    count_ups: AbilityMap,
+    pub(crate) count_downs: AbilityMap,
+    pub(crate) count_ups: AbilityMap,
     // still used?
     // indefinites: AbilityMap,
 }
@@ -122,8 +458,8 @@ impl Display for Effects {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
-pub(crate) enum Condition {
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Condition {
     Poor,
     Normal,
     Good,
@@ -167,6 +503,12 @@ pub(crate) struct State<'a> {
     pub heart_and_soul_used: bool,
     /// True if careful observation has been used
     pub careful_observation_uses: u8,
+    /// True if trained perfection has been used
+    pub trained_perfection_used: bool,
+    /// True if quick innovation has been used
+    pub quick_innovation_used: bool,
+    /// True for the step after a successful Hasty Touch from level 96, Daring Touch can only be used then
+    pub expedience: bool,
     /// Internal state variables set after each step.
     pub iq_cnt: i32,
     pub control: i32,
@@ -191,7 +533,7 @@ impl Display for State<'_> {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct Violations {
     pub progress_ok: bool,
@@ -208,6 +550,11 @@ impl Violations {
 }
 
 impl State<'_> {
+    /// Number of actions that led to this state, careful observation doesn't take a step but still ran
+    pub(crate) fn actions_used(&self) -> usize {
+        self.step as usize + self.careful_observation_uses as usize
+    }
+
     pub(crate) fn check_violations(&self) -> Violations {
         let progress_ok = self.progress_state >= self.synth.recipe.difficulty as i32;
         let cp_ok = self.cp_state >= 0;
@@ -290,6 +637,9 @@ impl<'a> From<&'a Synth> for State<'a> {
             touch_combo_step: 0,
             heart_and_soul_used: false,
             careful_observation_uses: 0,
+            trained_perfection_used: false,
+            quick_innovation_used: false,
+            expedience: false,
             iq_cnt: 0,
             control: 0,
             quality_gain: 0,
@@ -376,6 +726,7 @@ struct ModifierResult {
 }
 
 /// I could just do the functions that the JS uses, but I have lifetimes to worry about.
+#[derive(Clone)]
 pub(crate) enum SimulationCondition {
     Simulation {
         ignore_condition: bool,
@@ -384,12 +735,14 @@ pub(crate) enum SimulationCondition {
         pp_good: f32,
         pp_excellent: f32,
     },
+    /// Rolls conditions and action success from the rng instead of using expected values
+    MonteCarlo { rng: Box<ChaCha8Rng> },
 }
 
 impl SimulationCondition {
-    pub(crate) fn new_sim_condition() -> SimulationCondition {
+    pub(crate) fn new_sim_condition(synth: &Synth) -> SimulationCondition {
         SimulationCondition::Simulation {
-            ignore_condition: true,
+            ignore_condition: !synth.use_conditions,
             pp_poor: 0.0,
             pp_normal: 1.0,
             pp_good: 0.0,
@@ -398,7 +751,13 @@ impl SimulationCondition {
         }
     }
 
-    fn update(&mut self, p_good: f32, p_excellent: f32) {
+    pub(crate) fn new_monte_carlo(seed: u64) -> SimulationCondition {
+        SimulationCondition::MonteCarlo {
+            rng: Box::new(ChaCha8Rng::seed_from_u64(seed)),
+        }
+    }
+
+    fn update(&mut self, condition: &mut Condition, p_good: f32, p_excellent: f32) {
         match self {
             SimulationCondition::Simulation {
                 ignore_condition,
@@ -414,12 +773,46 @@ impl SimulationCondition {
                     *pp_normal = 1.0 - (*pp_good + *pp_excellent + *pp_poor);
                 }
             }
+            SimulationCondition::MonteCarlo { rng } => {
+                *condition = match condition {
+                    Condition::Excellent => Condition::Poor,
+                    Condition::Good | Condition::Poor => Condition::Normal,
+                    Condition::Normal => {
+                        let roll: f32 = rng.gen();
+                        if roll < p_excellent {
+                            Condition::Excellent
+                        } else if roll < p_excellent + p_good {
+                            Condition::Good
+                        } else {
+                            Condition::Normal
+                        }
+                    }
+                };
+            }
         }
     }
 
-    fn check_good_or_excellent(&self, _: &State) -> bool {
+    /// Expected value simulations use the probability as is, monte carlo rolls for a full success or a failure
+    fn roll_success(&mut self, success_probability: f32) -> f32 {
         match self {
+            SimulationCondition::Simulation { .. } => success_probability,
+            SimulationCondition::MonteCarlo { rng } => {
+                if rng.gen::<f32>() <= success_probability {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+
+    fn check_good_or_excellent(&self, state: &State) -> bool {
+        match self {
+            // the action is weighted by p_good_or_excellent instead, which is 1 when conditions are ignored
             SimulationCondition::Simulation { .. } => true,
+            SimulationCondition::MonteCarlo { .. } => {
+                matches!(state.condition, Condition::Good | Condition::Excellent)
+            }
         }
     }
 
@@ -437,6 +830,7 @@ impl SimulationCondition {
                     pp_excellent + pp_good
                 }
             }
+            SimulationCondition::MonteCarlo { .. } => 1.0,
         }
     }
 }
@@ -471,6 +865,13 @@ impl<'a> State<'a> {
             }
         }
 
+        // Daring Touch relies on Expedience, which only comes from a successful Hasty Touch
+        if action == Action::DaringTouch {
+            if let SimulationCondition::Simulation { .. } = condition {
+                success_probability *= Action::HastyTouch.details().success_probability;
+            }
+        }
+
         success_probability = success_probability.min(1.0);
 
         // Advanced Touch Combo
@@ -589,6 +990,13 @@ impl<'a> State<'a> {
             }
         }
 
+        // Daring Touch can only be used while Expedience is active
+        if action == Action::DaringTouch && !self.expedience {
+            self.wasted_actions += 1.0;
+            quality_gain = 0;
+            cp_cost = 0;
+        }
+
         // Effects modifying durability cost
         let mut durability_cost = action_details.durability_cost as f32;
         if self.effects.count_downs.get(Action::WasteNot).is_some()
@@ -605,6 +1013,12 @@ impl<'a> State<'a> {
             }
         }
 
+        // Trained Perfection removes the durability cost of the next action that has one
+        if durability_cost > 0.0 && self.effects.count_ups.get(Action::TrainedPerfection).is_some() {
+            durability_cost = 0.0;
+            self.effects.count_ups.remove(Action::TrainedPerfection);
+        }
+
         if self.durability_state < durability_cost as i32
             && (action == Action::Groundwork || action == Action::Groundwork2)
         {
@@ -622,10 +1036,11 @@ impl<'a> State<'a> {
             }
         }
 
-        // We can only use Precise Touch when state material condition is Good or Excellent. Default is true for probabilistic method.
-        if action.eq(&Action::PreciseTouch) {
+        // We can only use Precise Touch when state material condition is Good or Excellent.
+        // Unless we've used Heart and Soul, then the condition check is always good.
+        if action.eq(&Action::PreciseTouch) && self.action != Some(HeartAndSoul) {
             if condition.check_good_or_excellent(self) {
-                quality_gain *= condition.p_good_or_excellent() as u32;
+                quality_gain = (quality_gain as f32 * condition.p_good_or_excellent()) as u32;
             } else {
                 self.wasted_actions += 1.0;
                 quality_gain = 0;
@@ -667,6 +1082,13 @@ impl<'a> State<'a> {
             }
         }
 
+        if action == Action::ImmaculateMend {
+            self.durability_state = self.synth.recipe.durability as i32;
+            if self.synth.solver_vars.solve_for_completion {
+                self.wasted_actions += 50.0;
+            }
+        }
+
         if self.effects.count_downs.get(Action::Manipulation).is_some()
             && self.durability_state > 0
             && action != Action::Manipulation
@@ -715,11 +1137,32 @@ impl<'a> State<'a> {
         }
 
         // Manage effects with conditional requirements
-        // Can't use heart and soul or careful observation without being a specialist
-        if !self.synth.crafter.specialist && (action == HeartAndSoul || action == CarefulObservation) {
+        // Can't use heart and soul, careful observation or quick innovation without being a specialist
+        if !self.synth.crafter.specialist
+            && (action == HeartAndSoul
+                || action == CarefulObservation
+                || action == Action::QuickInnovation)
+        {
             self.wasted_actions += 100.0;
         }
 
+        // Trained perfection and quick innovation are once per craft
+        if action == Action::TrainedPerfection {
+            if self.trained_perfection_used {
+                self.wasted_actions += 100.0;
+            }
+            self.trained_perfection_used = true;
+        }
+
+        if action == Action::QuickInnovation {
+            if self.quick_innovation_used
+                || self.effects.count_downs.get(Action::Innovation).is_some()
+            {
+                self.wasted_actions += 100.0;
+            }
+            self.quick_innovation_used = true;
+        }
+
         // Handle double uses of HeartAndSoul
         if action == HeartAndSoul {
             if self.heart_and_soul_used {
@@ -737,9 +1180,11 @@ impl<'a> State<'a> {
         }
 
         let can_only_use_excellent_or_good = action_details.on_excellent || action_details.on_good;
-        let can_use_on_excellent_or_good = self.action == Some(HeartAndSoul);
+        let can_use_on_excellent_or_good = self.cp_state > 0
+            && (self.action == Some(HeartAndSoul) || condition.check_good_or_excellent(self));
         if can_only_use_excellent_or_good {
             if can_use_on_excellent_or_good {
+                self.trick_uses += 1;
                 if action == Action::TricksOfTheTrade {
                     self.cp_state += (20.0 * condition.p_good_or_excellent()) as i32;
                 }
@@ -781,13 +1226,25 @@ impl<'a> State<'a> {
                 }
             }
             // Increment inner quiet countups that have conditional requirements
-            else if action == Action::PreciseTouch && condition.check_good_or_excellent(self) {
-                let quiet_increment =
-                    (2.0 * success_probability * condition.p_good_or_excellent()) as i32;
+            else if action == Action::PreciseTouch
+                && (self.action == Some(HeartAndSoul) || condition.check_good_or_excellent(self))
+            {
+                let p_good_or_excellent = if self.action == Some(HeartAndSoul) {
+                    1.0
+                } else {
+                    condition.p_good_or_excellent()
+                };
+                let quiet_increment = (2.0 * success_probability * p_good_or_excellent) as i32;
                 if let Some(quiet) = self.effects.count_ups.get_mut(Action::InnerQuiet) {
                     *quiet += quiet_increment as i8;
                 }
             }
+            // Refined Touch gives an extra stack when it follows Basic Touch
+            else if action == Action::RefinedTouch && self.action == Some(Action::BasicTouch) {
+                if let Some(quiet) = self.effects.count_ups.get_mut(Action::InnerQuiet) {
+                    *quiet += (2.0 * success_probability) as i8;
+                }
+            }
             // Increment all other inner quiet count ups
             else if action_details.quality_increase_multiplier > 0.0
                 && action != Action::Reflect
@@ -805,7 +1262,9 @@ impl<'a> State<'a> {
         }
 
         // Initialize new effects after countdowns are managed to reset them properly
-        if action_details.action_type == ActionType::CountUp {
+        if action_details.action_type == ActionType::CountUp
+            && self.effects.count_ups.get(action).is_none()
+        {
             self.effects.count_ups.insert(action, 0);
         }
 
@@ -816,6 +1275,18 @@ impl<'a> State<'a> {
                 self.effects.count_downs.insert(action, active_turns as i8);
             }
         }
+
+        // Expedience from Hasty Touch lasts for the next step
+        self.expedience = action == Action::HastyTouch
+            && self.synth.crafter.level >= 96
+            && success_probability > 0.0;
+
+        // Quick Innovation grants a single step of Innovation
+        if action == Action::QuickInnovation
+            && self.effects.count_downs.get(Action::Innovation).is_none()
+        {
+            self.effects.count_downs.insert(Action::Innovation, 1);
+        }
     }
 
     fn update_state(
@@ -883,17 +1354,25 @@ impl<'a> State<'a> {
                         + 0.5 * *pp_poor;
                 }
             }
+            SimulationCondition::MonteCarlo { .. } => {
+                condition_quality_increase_multiplier *= match state.condition {
+                    Condition::Excellent => 4.0,
+                    Condition::Good => 1.5,
+                    Condition::Poor => 0.5,
+                    Condition::Normal => 1.0,
+                };
+            }
         }
 
         let result = state.apply_modifiers(action, sim_condition);
         state.base_quality_gain = result.quality_gain;
         state.base_progress_gain = result.progress_gain;
         // Calculate final gains / losses
-        let success_probability = result.success_probability;
+        let success_probability = sim_condition.roll_success(result.success_probability);
         // no assume success for now
         let mut progress_gain = result.progress_gain;
         if progress_gain > 0 {
-            state.reliability = (state.reliability as f32 * success_probability) as i32;
+            state.reliability = (state.reliability as f32 * result.success_probability) as i32;
         }
 
         progress_gain = (success_probability * progress_gain as f32) as u32;
@@ -908,10 +1387,10 @@ impl<'a> State<'a> {
             result.durability_cost as i32,
             result.cp_cost,
             sim_condition,
-            result.success_probability,
+            success_probability,
         );
 
-        sim_condition.update(p_good, p_excellent);
+        sim_condition.update(&mut state.condition, p_good, p_excellent);
         state.action = Some(action);
         state
     }
@@ -920,10 +1399,63 @@ impl<'a> State<'a> {
 #[cfg(test)]
 mod test {
     use crate::actions::Action;
-    use crate::xiv_model::{SimulationCondition, State, Synth};
+    use crate::xiv_model::{
+        ClassStats, Crafter, CrafterProfile, CraftingClass, SimulationCondition, State, Synth,
+    };
 
     const CRAFTER_SYNTH: &str = r#"{"crafter":{"level":78,"craftsmanship":863,"control":877,"cp":412,"actions":["muscleMemory","reflect","basicSynth2","carefulSynthesis","groundwork","intensiveSynthesis","delicateSynthesis","basicTouch","standardTouch","byregotsBlessing","preciseTouch","prudentTouch","preparatoryTouch","tricksOfTheTrade","mastersMend","wasteNot","wasteNot2","veneration","greatStrides","innovation","finalAppraisal","observe"]},"recipe":{"cls":"Weaver","level":390,"difficulty":1195,"durability":60,"startQuality":0,"safetyMargin":0,"maxQuality":3010,"baseLevel":71,"progressDivider":101,"progressModifier":100,"qualityDivider":81,"qualityModifier":100,"suggestedControl":1220,"suggestedCraftsmanship":1320,"name":"Custom Gathering Tool Components"},"sequence":[],"algorithm":"eaComplex","maxTricksUses":0,"maxMontecarloRuns":400,"reliabilityPercent":100,"useConditions":false,"maxLength":50,"solver":{"algorithm":"eaComplex","penaltyWeight":10000,"population":200000,"subPopulations":10,"solveForCompletion":false,"remainderCPFitnessValue":10,"remainderDurFitnessValue":100,"maxStagnationCounter":25,"generations":2000},"debug":true}"#;
 
+    #[test]
+    fn parses_class() {
+        let synth: Synth = serde_json::from_str(CRAFTER_SYNTH).unwrap();
+        assert_eq!(synth.recipe.cls, Some(CraftingClass::Weaver));
+        assert_eq!(synth.crafter.cls, None);
+        assert_eq!("culinarian".parse(), Ok(CraftingClass::Culinarian));
+        assert!("Botanist".parse::<CraftingClass>().is_err());
+    }
+
+    #[test]
+    fn synth_from_profile() {
+        let synth: Synth = serde_json::from_str(CRAFTER_SYNTH).unwrap();
+        let profile: CrafterProfile = serde_json::from_str(
+            r#"{"Weaver":{"level":78,"craftsmanship":863,"control":877,"cp":412,"specialist":true,"actions":["basicSynth","basicTouch"]},
+                "Alchemist":{"level":50,"craftsmanship":200,"control":180,"cp":250}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            profile.classes[&CraftingClass::Alchemist],
+            ClassStats {
+                level: 50,
+                craftsmanship: 200,
+                control: 180,
+                cp: 250,
+                specialist: false,
+                actions: vec![],
+            }
+        );
+
+        let weaver =
+            Synth::from_profile(&profile, synth.recipe.clone(), synth.solver_vars.clone()).unwrap();
+        assert_eq!(weaver.crafter.cls, Some(CraftingClass::Weaver));
+        assert_eq!(weaver.crafter.craft_points, 412);
+        assert!(weaver.crafter.specialist);
+        assert_eq!(
+            weaver.crafter.actions,
+            vec![Action::BasicSynth, Action::BasicTouch]
+        );
+        assert_eq!(weaver.reliability_percent, 100);
+        assert_eq!(weaver.solver_vars.population, synth.solver_vars.population);
+
+        let mut recipe = synth.recipe.clone();
+        recipe.cls = Some(CraftingClass::Alchemist);
+        let alchemist = Synth::from_profile(&profile, recipe.clone(), Default::default()).unwrap();
+        assert_eq!(alchemist.crafter.level, 50);
+        recipe.cls = Some(CraftingClass::Carpenter);
+        assert!(Synth::from_profile(&profile, recipe.clone(), Default::default()).is_none());
+        recipe.cls = None;
+        assert!(Synth::from_profile(&profile, recipe, Default::default()).is_none());
+    }
+
     #[test]
     fn basic_action_sim() {
         let synth: Synth = serde_json::from_str(CRAFTER_SYNTH).unwrap();
@@ -943,4 +1475,200 @@ mod test {
         let progress_touch = state.add_action(Action::BasicSynth, &mut simulation_condition);
         //assert_eq!(progress_touch.progress_state, 177);
     }
+
+    const LEVEL_100_SYNTH: &str = r#"{"crafter":{"level":100,"craftsmanship":4300,"control":4000,"cp":600,"specialist":true,"actions":["basicSynth2","carefulSynthesis2","groundwork2","basicTouch","hastyTouch","daringTouch","refinedTouch","quickInnovation","immaculateMend","trainedPerfection","innovation"]},"recipe":{"cls":"Weaver","level":690,"difficulty":6600,"durability":80,"startQuality":0,"safetyMargin":0,"maxQuality":12000,"baseLevel":100,"progressDivider":170,"progressModifier":90,"qualityDivider":150,"qualityModifier":75,"suggestedControl":3700,"suggestedCraftsmanship":3700,"name":"Level 100 Test Recipe"},"sequence":[],"algorithm":"eaComplex","maxTricksUses":0,"maxMontecarloRuns":400,"reliabilityPercent":100,"useConditions":false,"maxLength":50,"solver":{"algorithm":"eaComplex","penaltyWeight":10000,"population":200000,"subPopulations":10,"solveForCompletion":false,"remainderCPFitnessValue":10,"remainderDurFitnessValue":100,"maxStagnationCounter":25,"generations":2000},"debug":true}"#;
+
+    fn run<'a>(synth: &'a Synth, actions: &[Action]) -> State<'a> {
+        let mut condition = SimulationCondition::new_sim_condition(synth);
+        let mut state: State = synth.into();
+        for action in actions {
+            state = state.add_action(*action, &mut condition);
+        }
+        state
+    }
+
+    #[test]
+    fn simultaneous_buffs() {
+        let synth: Synth = serde_json::from_str(CRAFTER_SYNTH).unwrap();
+        let buffs = [
+            Action::WasteNot2,
+            Action::Manipulation,
+            Action::Veneration,
+            Action::FinalAppraisal,
+            Action::Innovation,
+            Action::GreatStrides,
+        ];
+        let state = run(&synth, &buffs);
+        for buff in buffs {
+            assert!(
+                state.effects.count_downs.get(buff).is_some(),
+                "{buff:?} dropped"
+            );
+        }
+        assert_eq!(state.effects.count_downs.iter().count(), buffs.len());
+
+        // every buff still applies to the next action
+        let touch = state.add_action(
+            Action::BasicTouch,
+            &mut SimulationCondition::new_sim_condition(&synth),
+        );
+        // waste not halves the cost and manipulation restores it
+        assert_eq!(touch.durability_state, 60);
+        assert!(touch
+            .effects
+            .count_downs
+            .get(Action::GreatStrides)
+            .is_none());
+        let plain = run(&synth, &[Action::BasicTouch]);
+        assert!(touch.quality_state > plain.quality_state * 2);
+    }
+
+    #[test]
+    fn immaculate_mend() {
+        let synth: Synth = serde_json::from_str(LEVEL_100_SYNTH).unwrap();
+        let state = run(
+            &synth,
+            &[
+                Action::Groundwork2,
+                Action::BasicTouch,
+                Action::ImmaculateMend,
+            ],
+        );
+        assert_eq!(state.durability_state, 80);
+        assert_eq!(state.cp_state, 600 - 18 - 18 - 112);
+    }
+
+    #[test]
+    fn trained_perfection() {
+        let synth: Synth = serde_json::from_str(LEVEL_100_SYNTH).unwrap();
+        let state = run(&synth, &[Action::TrainedPerfection, Action::Groundwork2]);
+        assert_eq!(state.durability_state, 80);
+        assert!(state
+            .effects
+            .count_ups
+            .get(Action::TrainedPerfection)
+            .is_none());
+        let state = run(
+            &synth,
+            &[
+                Action::TrainedPerfection,
+                Action::Groundwork2,
+                Action::Groundwork2,
+            ],
+        );
+        assert_eq!(state.durability_state, 60);
+        assert_eq!(state.wasted_actions, 0.0);
+
+        let twice = run(
+            &synth,
+            &[Action::TrainedPerfection, Action::TrainedPerfection],
+        );
+        assert!(twice.wasted_actions >= 100.0);
+    }
+
+    #[test]
+    fn daring_touch() {
+        let synth: Synth = serde_json::from_str(LEVEL_100_SYNTH).unwrap();
+        let alone = run(&synth, &[Action::DaringTouch]);
+        assert_eq!(alone.quality_state, 0);
+        assert!(alone.wasted_actions > 0.0);
+
+        let combo = run(&synth, &[Action::HastyTouch, Action::DaringTouch]);
+        let hasty = run(&synth, &[Action::HastyTouch]);
+        assert!(hasty.expedience);
+        // expedience is part of the state, not an action a rotation can use
+        assert!(serde_json::from_str::<Action>(r#""expedience""#).is_err());
+        assert!(hasty.effects.count_downs.get(Action::HastyTouch).is_none());
+        assert!(combo.quality_state > hasty.quality_state);
+        assert_eq!(combo.wasted_actions, 0.0);
+
+        // expedience only lasts for one step
+        let late = run(
+            &synth,
+            &[Action::HastyTouch, Action::BasicTouch, Action::DaringTouch],
+        );
+        assert!(late.wasted_actions > 0.0);
+    }
+
+    #[test]
+    fn quick_innovation() {
+        let synth: Synth = serde_json::from_str(LEVEL_100_SYNTH).unwrap();
+        let plain = run(&synth, &[Action::BasicTouch]);
+        let buffed = run(&synth, &[Action::QuickInnovation, Action::BasicTouch]);
+        assert_eq!(buffed.wasted_actions, 0.0);
+        assert!(buffed.quality_state > plain.quality_state);
+        assert!(buffed.effects.count_downs.get(Action::Innovation).is_none());
+
+        let during_innovation = run(&synth, &[Action::Innovation, Action::QuickInnovation]);
+        assert!(during_innovation.wasted_actions >= 100.0);
+
+        let non_specialist = Synth {
+            crafter: Crafter {
+                specialist: false,
+                ..synth.crafter.clone()
+            },
+            ..synth.clone()
+        };
+        assert!(run(&non_specialist, &[Action::QuickInnovation]).wasted_actions >= 100.0);
+    }
+
+    #[test]
+    fn refined_touch() {
+        let synth: Synth = serde_json::from_str(LEVEL_100_SYNTH).unwrap();
+        let iq = |state: &State| state.effects.count_ups.get(Action::InnerQuiet).unwrap().1 + 1;
+        assert_eq!(
+            iq(&run(&synth, &[Action::BasicTouch, Action::RefinedTouch])),
+            3
+        );
+        assert_eq!(
+            iq(&run(&synth, &[Action::HastyTouch, Action::RefinedTouch])),
+            1
+        );
+    }
+
+    #[test]
+    fn ignored_conditions() {
+        let synth: Synth = serde_json::from_str(CRAFTER_SYNTH).unwrap();
+        assert!(!synth.use_conditions);
+        let mut condition = SimulationCondition::new_sim_condition(&synth);
+        let state: State = (&synth).into();
+        let state = state.add_action(Action::BasicTouch, &mut condition);
+        // ignoring conditions treats every step as good enough for precise touch, like the JS
+        let precise = state.add_action(Action::PreciseTouch, &mut condition);
+        let basic = state.add_action(Action::BasicTouch, &mut condition);
+        assert!(precise.quality_state > basic.quality_state);
+        assert_eq!(precise.wasted_actions, state.wasted_actions);
+        assert_eq!(precise.trick_uses, 1);
+    }
+
+    #[test]
+    fn expected_value_conditions() {
+        let mut synth: Synth = serde_json::from_str(
+            &CRAFTER_SYNTH.replace(r#""useConditions":false"#, r#""useConditions":true"#),
+        )
+        .unwrap();
+        synth.max_trick_uses = 1;
+        assert!(synth.use_conditions);
+        let mut ignored = SimulationCondition::new_sim_condition(&Synth {
+            use_conditions: false,
+            ..synth.clone()
+        });
+        let mut condition = SimulationCondition::new_sim_condition(&synth);
+        let mut state: State = (&synth).into();
+        let mut ignored_state: State = (&synth).into();
+        for action in [Action::Observe, Action::Observe, Action::BasicTouch] {
+            state = state.add_action(action, &mut condition);
+            ignored_state = ignored_state.add_action(action, &mut ignored);
+        }
+        // the chance of good and excellent conditions raises the expected quality
+        assert!(state.quality_state > ignored_state.quality_state);
+
+        // precise touch is weighted by the chance of a good or excellent condition
+        let precise = state.add_action(Action::PreciseTouch, &mut condition);
+        let basic = state.add_action(Action::BasicTouch, &mut condition);
+        let gain = precise.quality_state - state.quality_state;
+        assert!(gain > 0);
+        assert!(gain < basic.quality_state - state.quality_state);
+        assert_eq!(precise.trick_uses, 1);
+    }
 }